@@ -0,0 +1,183 @@
+use crate::{color::Color, render::Renderer, unit::Coordinates};
+
+/// the part of the map shown on screen. It follows a target but only moves once the target
+/// leaves the dead zone in the middle of the view, so small steps do not scroll the map
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    /// world position of the top left corner of the view
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    /// size of the dead zone, centered in the view
+    dead_zone: (i32, i32),
+}
+
+impl Camera {
+    pub fn new(width: i32, height: i32, dead_zone: (i32, i32)) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            dead_zone: (dead_zone.0.min(width), dead_zone.1.min(height)),
+        }
+    }
+
+    /// move just enough to bring `target` back into the dead zone, or center on it when it is
+    /// not on screen at all, like after a level change. The view never goes past the map edges
+    pub fn follow(&mut self, target: &Coordinates, (map_width, map_height): (i32, i32)) {
+        if self.to_screen(target.x, target.y).is_none() {
+            self.x = target.x - self.width / 2;
+            self.y = target.y - self.height / 2;
+        } else {
+            let (zone_width, zone_height) = self.dead_zone;
+            let left = self.x + (self.width - zone_width) / 2;
+            let top = self.y + (self.height - zone_height) / 2;
+
+            if target.x < left {
+                self.x -= left - target.x;
+            } else if target.x >= left + zone_width {
+                self.x += target.x - (left + zone_width - 1);
+            }
+
+            if target.y < top {
+                self.y -= top - target.y;
+            } else if target.y >= top + zone_height {
+                self.y += target.y - (top + zone_height - 1);
+            }
+        }
+
+        self.x = self.x.min(map_width - self.width).max(0);
+        self.y = self.y.min(map_height - self.height).max(0);
+    }
+
+    /// console cell of the world tile, `None` when it is outside the view
+    pub fn to_screen(self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let (screen_x, screen_y) = (x - self.x, y - self.y);
+
+        if screen_x < 0 || screen_x >= self.width || screen_y < 0 || screen_y >= self.height {
+            return None;
+        }
+
+        Some((screen_x, screen_y))
+    }
+
+    /// world tile under the console cell
+    pub fn to_world(self, screen_x: i32, screen_y: i32) -> Coordinates {
+        Coordinates {
+            x: screen_x + self.x,
+            y: screen_y + self.y,
+        }
+    }
+
+    /// renderer that draws world positions through this camera onto `renderer`
+    pub fn view<'a>(&'a self, renderer: &'a mut dyn Renderer) -> View<'a> {
+        View {
+            camera: self,
+            renderer,
+        }
+    }
+}
+
+/// renderer that takes world positions, shifts them into the camera's view and drops whatever
+/// falls outside of it
+pub struct View<'a> {
+    camera: &'a Camera,
+    renderer: &'a mut dyn Renderer,
+}
+
+impl Renderer for View<'_> {
+    fn put_char(&mut self, x: i32, y: i32, char: char, color: Color) {
+        if let Some((x, y)) = self.camera.to_screen(x, y) {
+            self.renderer.put_char(x, y, char, color);
+        }
+    }
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color) {
+        if let Some((x, y)) = self.camera.to_screen(x, y) {
+            self.renderer.set_background(x, y, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP_SIZE: (i32, i32) = (100, 60);
+
+    fn camera() -> Camera {
+        Camera::new(20, 10, (6, 4))
+    }
+
+    fn at(x: i32, y: i32) -> Coordinates {
+        Coordinates { x, y }
+    }
+
+    /// world position of the top left corner of the view
+    fn corner(camera: Camera) -> Coordinates {
+        camera.to_world(0, 0)
+    }
+
+    #[test]
+    fn the_view_does_not_scroll_inside_the_dead_zone() {
+        let mut camera = camera();
+        camera.follow(&at(50, 30), MAP_SIZE);
+        assert_eq!(corner(camera), at(40, 25));
+
+        // the dead zone spans 47..53 and 28..32
+        for target in [at(47, 28), at(52, 31), at(50, 30)] {
+            camera.follow(&target, MAP_SIZE);
+            assert_eq!(corner(camera), at(40, 25));
+        }
+
+        camera.follow(&at(53, 32), MAP_SIZE);
+        assert_eq!(corner(camera), at(41, 26));
+    }
+
+    #[test]
+    fn the_view_stops_at_the_map_edges() {
+        let (width, height) = MAP_SIZE;
+
+        for (target, expected) in [
+            (at(0, 0), at(0, 0)),
+            (at(width - 1, 0), at(width - 20, 0)),
+            (at(0, height - 1), at(0, height - 10)),
+            (at(width - 1, height - 1), at(width - 20, height - 10)),
+        ] {
+            let mut camera = camera();
+            camera.follow(&at(50, 30), MAP_SIZE);
+            camera.follow(&target, MAP_SIZE);
+
+            assert_eq!(corner(camera), expected);
+        }
+    }
+
+    #[test]
+    fn a_map_smaller_than_the_view_stays_in_the_corner() {
+        let mut camera = camera();
+
+        for target in [at(0, 0), at(9, 4), at(5, 2)] {
+            camera.follow(&target, (10, 5));
+            assert_eq!(corner(camera), at(0, 0));
+        }
+    }
+
+    #[test]
+    fn screen_positions_map_back_to_the_same_tile() {
+        let mut camera = camera();
+        camera.follow(&at(50, 30), MAP_SIZE);
+
+        for x in 35..65 {
+            for y in 20..40 {
+                match camera.to_screen(x, y) {
+                    Some((screen_x, screen_y)) => {
+                        assert_eq!(camera.to_world(screen_x, screen_y), at(x, y))
+                    }
+                    None => assert!(!(40..60).contains(&x) || !(25..35).contains(&y)),
+                }
+            }
+        }
+    }
+}
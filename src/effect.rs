@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::{self, Color};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EffectKind {
+    /// loses `power` hit points at the end of each turn, and hits a quarter weaker
+    Poison,
+    /// loses its turns, and takes half as much damage again from hits
+    Stun,
+    /// heals `power` hit points at the end of each turn
+    Regeneration,
+    /// gains `power` speed
+    Haste,
+    /// loses `power` hit points at the end of each turn
+    Burning,
+}
+
+/// a timed effect on a unit, it wears off after `turns` turns of that unit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusEffect {
+    pub kind: EffectKind,
+    pub turns: i32,
+    /// strength of the effect, unused by kinds that do not scale
+    #[serde(default)]
+    pub power: i32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: EffectKind, turns: i32, power: i32) -> Self {
+        Self { kind, turns, power }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.turns <= 0 {
+            return Err(format!("turns must be positive, got {}", self.turns));
+        }
+
+        match self.kind {
+            EffectKind::Stun if self.power != 0 => Err(format!(
+                "power is not used by {:?}, got {}",
+                self.kind, self.power
+            )),
+            EffectKind::Poison
+            | EffectKind::Regeneration
+            | EffectKind::Haste
+            | EffectKind::Burning
+                if self.power <= 0 =>
+            {
+                Err(format!(
+                    "power must be positive for {:?}, got {}",
+                    self.kind, self.power
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// what the unit is while under the effect, as in "Orc is poisoned"
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            EffectKind::Poison => "poisoned",
+            EffectKind::Stun => "stunned",
+            EffectKind::Regeneration => "regenerating",
+            EffectKind::Haste => "hasted",
+            EffectKind::Burning => "burning",
+        }
+    }
+
+    /// icon shown in the status panel
+    pub fn icon(&self) -> (char, Color) {
+        match self.kind {
+            EffectKind::Poison => ('~', color::LIGHT_GREEN),
+            EffectKind::Stun => ('*', color::LIGHT_YELLOW),
+            EffectKind::Regeneration => ('+', color::LIGHT_RED),
+            EffectKind::Haste => ('>', color::LIGHT_SKY),
+            EffectKind::Burning => ('^', color::ORANGE),
+        }
+    }
+
+    /// whether the unit loses the turn it is about to take
+    pub fn on_turn_start(&self) -> bool {
+        self.kind == EffectKind::Stun
+    }
+
+    /// hit points the unit gains, or loses when negative, once its turn is over
+    pub fn on_turn_end(&self) -> i32 {
+        match self.kind {
+            EffectKind::Poison | EffectKind::Burning => -self.power,
+            EffectKind::Regeneration => self.power,
+            _ => 0,
+        }
+    }
+
+    /// damage the unit takes from a hit that would deal `damage`
+    pub fn on_damage(&self, damage: i32) -> i32 {
+        match self.kind {
+            EffectKind::Stun => damage + damage / 2,
+            _ => damage,
+        }
+    }
+
+    /// power of an attack the unit makes with `power`
+    pub fn on_attack(&self, power: i32) -> i32 {
+        match self.kind {
+            EffectKind::Poison => power * 3 / 4,
+            _ => power,
+        }
+    }
+
+    pub fn modify_speed(&self, speed: i32) -> i32 {
+        match self.kind {
+            EffectKind::Haste => speed + self.power,
+            _ => speed,
+        }
+    }
+
+    pub fn modify_defense(&self, defense: i32) -> i32 {
+        match self.kind {
+            EffectKind::Stun => defense / 2,
+            _ => defense,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{path::line, unit::Coordinates};
+
+/// field of view by ray casting: a ray is cast from `origin` to every tile on the edge of the
+/// square around it and stops at the first tile that blocks sight, which is itself lit.
+///
+/// Returns the visibility of every tile of the `width` x `height` grid, indexed by `x + y * width`
+pub fn compute_fov(
+    origin: &Coordinates,
+    radius: i32,
+    (width, height): (i32, i32),
+    is_transparent: impl Fn(i32, i32) -> bool,
+) -> Vec<bool> {
+    let mut visible = vec![false; (width * height) as usize];
+
+    let is_on_grid = |x: i32, y: i32| x >= 0 && x < width && y >= 0 && y < height;
+
+    if !is_on_grid(origin.x, origin.y) {
+        return visible;
+    }
+
+    visible[(origin.x + origin.y * width) as usize] = true;
+
+    let mut edge = vec![];
+
+    for offset in -radius..=radius {
+        edge.push((offset, -radius));
+        edge.push((offset, radius));
+        edge.push((-radius, offset));
+        edge.push((radius, offset));
+    }
+
+    for (dx, dy) in edge {
+        let target = Coordinates {
+            x: origin.x + dx,
+            y: origin.y + dy,
+        };
+
+        for point in line(origin, &target) {
+            let (x, y) = (point.x - origin.x, point.y - origin.y);
+
+            if !is_on_grid(point.x, point.y) || x.pow(2) + y.pow(2) > radius.pow(2) {
+                break;
+            }
+
+            visible[(point.x + point.y * width) as usize] = true;
+
+            if !is_transparent(point.x, point.y) {
+                break;
+            }
+        }
+    }
+
+    visible
+}
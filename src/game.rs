@@ -0,0 +1,646 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::{self, Color},
+    effect::{EffectKind, StatusEffect},
+    generator::GeneratorKind,
+    map::Map,
+    monsters::MonsterTemplates,
+    unit::{Coordinates, Stat},
+};
+
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub text: String,
+    pub color: Color,
+}
+
+impl Message {
+    pub fn new<T: Into<String>>(text: T, color: Color) -> Self {
+        Self {
+            text: text.into(),
+            color,
+        }
+    }
+}
+
+/// what the player asks the game to do, independent of the keys or buttons that produced it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Move {
+        dx: i32,
+        dy: i32,
+    },
+    PickUp,
+    UseItem(usize),
+    /// cast the spell from the inventory at the target tile
+    UseItemOn {
+        index: usize,
+        target: Coordinates,
+    },
+    /// throw the item from the inventory at the target tile
+    Throw {
+        index: usize,
+        target: Coordinates,
+    },
+    /// shoot at the target tile with the worn ranged weapon
+    Fire {
+        target: Coordinates,
+    },
+    DropItem(usize),
+    Unequip(usize),
+    /// close the open doors next to the player
+    CloseDoors,
+    /// take one step towards the explored tile
+    Travel {
+        target: Coordinates,
+    },
+    /// take one step towards the closest unexplored part of the level
+    Explore,
+    /// let a turn pass
+    Wait,
+    /// let a turn pass to regain health, refused at full health or with monsters in view
+    Rest,
+    /// spend a pending level up on the stat
+    LevelUp(Stat),
+    Descend,
+    Ascend,
+}
+
+impl Command {
+    /// whether the command is one step of a longer errand the frontend repeats until the player
+    /// is disturbed
+    pub fn repeats(&self) -> bool {
+        matches!(
+            self,
+            Command::Travel { .. } | Command::Explore | Command::Rest
+        )
+    }
+}
+
+/// what happened while a command was carried out, for the frontend to present
+#[derive(Clone, Debug)]
+pub enum Event {
+    Message(Message),
+    /// the command used up the player's turn and the monsters acted
+    TurnTaken,
+    /// the player has enough experience for the next level and has to choose a stat to raise
+    LevelUp,
+    PlayerDied,
+    /// something happened that should stop a repeating command, the reason is in the messages
+    Disturbed,
+}
+
+/// what the player keeps an eye on while carrying out a repeating command
+struct Surroundings {
+    monsters: Vec<usize>,
+    items: Vec<Coordinates>,
+    hp: i32,
+}
+
+impl Surroundings {
+    fn of(map: &Map) -> Self {
+        Self {
+            monsters: map.visible_monster_ids(),
+            items: map.visible_items(),
+            hp: map.player().current_hp(),
+        }
+    }
+
+    /// why the player stops, if anything changed for the worse since `self`
+    fn disturbance(&self, now: &Surroundings) -> Option<&'static str> {
+        if has_newcomer(&self.monsters, &now.monsters) {
+            Some("You stop, something comes into view")
+        } else if has_newcomer(&self.items, &now.items) {
+            Some("You stop, you spot an item")
+        } else if now.hp < self.hp {
+            Some("You stop, you are hurt")
+        } else {
+            None
+        }
+    }
+}
+
+/// whether anything in `now` was not there `before`
+fn has_newcomer<T: PartialEq>(before: &[T], now: &[T]) -> bool {
+    now.iter().any(|element| !before.contains(element))
+}
+
+/// seed of the level at `depth`, derived from the game seed so that every level is reproducible
+fn level_seed(seed: u64, depth: u32) -> u64 {
+    seed.wrapping_add((depth as u64 - 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+/// damage taken by falling through a chasm to the level below
+const FALL_DAMAGE: i32 = 5;
+/// turns the landing leaves the player stunned for, counting the one the fall happened in
+const FALL_STUN_TURNS: i32 = 3;
+
+/// every level at a multiple of this depth is a cave, whatever generator the game uses otherwise
+const CAVE_DEPTH_INTERVAL: u32 = 3;
+
+/// generator of the level at `depth`
+fn level_generator(generator: GeneratorKind, depth: u32) -> GeneratorKind {
+    if depth.is_multiple_of(CAVE_DEPTH_INTERVAL) {
+        GeneratorKind::Caves
+    } else {
+        generator
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    seed: u64,
+    width: i32,
+    height: i32,
+    /// visited levels, `levels[depth - 1]` is the level at `depth`
+    levels: Vec<Map>,
+    depth: u32,
+    /// builds the new levels of this game, apart from the cave levels
+    generator: GeneratorKind,
+    /// not saved, the current data file is used for levels generated after loading
+    #[serde(skip)]
+    templates: MonsterTemplates,
+}
+
+impl Game {
+    pub fn new(
+        width: i32,
+        height: i32,
+        seed: u64,
+        generator: GeneratorKind,
+        templates: MonsterTemplates,
+    ) -> Self {
+        let mut map = Map::new(
+            width,
+            height,
+            level_seed(seed, 1),
+            1,
+            &templates,
+            level_generator(generator, 1).generator().as_ref(),
+        );
+        map.update_fov();
+
+        Self {
+            seed,
+            width,
+            height,
+            levels: vec![map],
+            depth: 1,
+            generator,
+            templates,
+        }
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.levels[self.depth as usize - 1]
+    }
+
+    pub fn map_mut(&mut self) -> &mut Map {
+        &mut self.levels[self.depth as usize - 1]
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// rebuild the non-serialized state of every visited level after loading
+    pub fn restore(&mut self, templates: MonsterTemplates) {
+        self.templates = templates;
+
+        for map in &mut self.levels {
+            map.restore();
+        }
+    }
+
+    /// carry out the player's command and let the monsters act if it took a turn.
+    /// This is the only entry point the frontend needs to drive the game
+    pub fn apply(&mut self, command: Command) -> Vec<Event> {
+        let mut events = vec![];
+
+        if !self.map().player().is_alive() {
+            return events;
+        }
+
+        let before = command.repeats().then(|| Surroundings::of(self.map()));
+
+        let took_turn = match command {
+            Command::Move { dx, dy } => self.map_mut().player_move_or_attack(dx, dy),
+            Command::PickUp => self.map_mut().pick_up_item(),
+            Command::UseItem(index) => self.map_mut().use_item(index),
+            Command::UseItemOn { index, target } => self.map_mut().use_item_on(index, target),
+            Command::Throw { index, target } => self.map_mut().throw_item(index, target),
+            Command::Fire { target } => self.map_mut().fire(target),
+            Command::DropItem(index) => self.map_mut().drop_item(index),
+            Command::Unequip(index) => self.map_mut().unequip_item(index),
+            Command::CloseDoors => self.map_mut().close_doors(),
+            Command::Travel { target } => self.map_mut().travel(target),
+            Command::Explore => self.map_mut().explore(),
+            Command::Wait => self.map_mut().wait_turn(),
+            Command::Rest => self.map_mut().rest(),
+            Command::LevelUp(stat) => {
+                self.map_mut().level_up_player(stat);
+
+                false
+            }
+            Command::Descend => {
+                self.descend();
+
+                false
+            }
+            Command::Ascend => {
+                self.ascend();
+
+                false
+            }
+        };
+
+        if self.map_mut().take_player_fell() {
+            self.fall();
+        }
+
+        if took_turn {
+            self.map_mut().monsters_action();
+            events.push(Event::TurnTaken);
+        }
+
+        self.map_mut().update_fov();
+
+        let disturbance = before
+            .filter(|_| took_turn)
+            .and_then(|before| before.disturbance(&Surroundings::of(self.map())));
+
+        if let Some(reason) = disturbance {
+            self.map_mut()
+                .add_message(Message::new(reason, color::LIGHT_YELLOW));
+        }
+
+        events.extend(
+            self.map_mut()
+                .take_messages()
+                .into_iter()
+                .map(Event::Message),
+        );
+
+        if disturbance.is_some() {
+            events.push(Event::Disturbed);
+        }
+
+        if !self.map().player().is_alive() {
+            events.push(Event::PlayerDied);
+        } else if self.map().player().can_level_up() {
+            events.push(Event::LevelUp);
+        }
+
+        events
+    }
+
+    /// go down the stairs the player is standing on, generating the next level on the first visit
+    pub fn descend(&mut self) -> bool {
+        let player = self.map().player().clone();
+
+        if !player
+            .get_position()
+            .is_equal(&self.map().get_down_stairs())
+        {
+            return false;
+        }
+
+        self.go_down();
+
+        let depth = self.depth;
+        let map = self.map_mut();
+        let arrival = map.get_up_stairs().unwrap_or_else(|| map.get_spawn_point());
+
+        map.place_player(player, arrival);
+        map.add_message(Message::new(
+            format!("You descend to depth {}", depth),
+            color::LIGHT_VIOLET,
+        ));
+
+        self.announce_caves();
+
+        true
+    }
+
+    /// drop the player through a chasm onto the level below, right under the chasm when the
+    /// tile there is safe to land on
+    fn fall(&mut self) {
+        let mut player = self.map().player().clone();
+        let mut messages = self.map_mut().take_messages();
+        let landing = *player.get_position();
+
+        self.go_down();
+
+        let depth = self.depth;
+        let map = self.map_mut();
+        let arrival = if map.is_safe_spot(landing.x, landing.y) {
+            landing
+        } else {
+            map.get_up_stairs().unwrap_or_else(|| map.get_spawn_point())
+        };
+
+        messages.push(Message::new(
+            format!("You fall through the chasm to depth {}", depth),
+            color::LIGHT_VIOLET,
+        ));
+        player.take_damage(FALL_DAMAGE, &mut messages);
+
+        if player.is_alive() {
+            player.add_effect(
+                StatusEffect::new(EffectKind::Stun, FALL_STUN_TURNS, 0),
+                &mut messages,
+            );
+        }
+
+        map.place_player(player, arrival);
+
+        for message in messages {
+            map.add_message(message);
+        }
+
+        self.announce_caves();
+    }
+
+    /// move one level down, generating it on the first visit
+    fn go_down(&mut self) {
+        self.depth += 1;
+
+        if self.levels.len() < self.depth as usize {
+            let map = Map::new(
+                self.width,
+                self.height,
+                level_seed(self.seed, self.depth),
+                self.depth,
+                &self.templates,
+                level_generator(self.generator, self.depth)
+                    .generator()
+                    .as_ref(),
+            );
+            self.levels.push(map);
+        }
+    }
+
+    fn announce_caves(&mut self) {
+        if level_generator(self.generator, self.depth) == GeneratorKind::Caves {
+            self.map_mut().add_message(Message::new(
+                "The worked stone gives way to damp natural caves.",
+                color::LIGHT_VIOLET,
+            ));
+        }
+    }
+
+    /// go up the stairs the player is standing on, back to the already visited level above
+    pub fn ascend(&mut self) -> bool {
+        let player = self.map().player().clone();
+
+        match self.map().get_up_stairs() {
+            Some(stairs) if player.get_position().is_equal(&stairs) => {}
+            _ => return false,
+        }
+
+        self.depth -= 1;
+
+        let depth = self.depth;
+        let map = self.map_mut();
+        let arrival = map.get_down_stairs();
+
+        map.place_player(player, arrival);
+        map.add_message(Message::new(
+            format!("You climb up to depth {}", depth),
+            color::LIGHT_VIOLET,
+        ));
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unit::Unit;
+
+    /// a game without monsters, so that nothing but the commands changes it
+    fn empty_game() -> Game {
+        Game::new(80, 50, 11, GeneratorKind::Bsp, MonsterTemplates::default())
+    }
+
+    fn texts(events: &[Event]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Message(message) => Some(message.text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn took_turn(events: &[Event]) -> bool {
+        events.iter().any(|event| matches!(event, Event::TurnTaken))
+    }
+
+    /// change the player outside of the commands, like the tests need to
+    fn edit_player(game: &mut Game, edit: impl FnOnce(&mut Unit)) {
+        let mut player = game.map().player().clone();
+        let position = *player.get_position();
+
+        edit(&mut player);
+        game.map_mut().place_player(player, position);
+    }
+
+    #[test]
+    fn every_depth_gets_its_own_seed() {
+        assert_ne!(level_seed(7, 1), level_seed(7, 2));
+        assert_ne!(level_seed(7, 2), level_seed(8, 2));
+        assert_eq!(level_seed(7, 1), 7);
+    }
+
+    /// repeat the command like the frontend does, until it is refused or the player is disturbed,
+    /// and return the events of that last step
+    fn repeat(game: &mut Game, command: Command) -> Vec<Event> {
+        for _ in 0..1000 {
+            let events = game.apply(command);
+
+            if !took_turn(&events) || events.iter().any(|event| matches!(event, Event::Disturbed)) {
+                return events;
+            }
+        }
+
+        panic!("{:?} went on without end", command);
+    }
+
+    fn poison(game: &mut Game) {
+        edit_player(game, |player| {
+            player.take_damage(10, &mut vec![]);
+            player.add_effect(StatusEffect::new(EffectKind::Poison, 5, 3), &mut vec![]);
+        });
+    }
+
+    /// a game with the shipped monsters
+    fn game(seed: u64) -> Game {
+        Game::new(
+            80,
+            50,
+            seed,
+            GeneratorKind::Bsp,
+            MonsterTemplates::bundled(),
+        )
+    }
+
+    #[test]
+    fn exploring_stops_when_a_monster_comes_into_view() {
+        let mut game = game(2);
+        assert!(game.map().visible_monster_ids().is_empty());
+
+        let events = repeat(&mut game, Command::Explore);
+
+        assert!(texts(&events).contains(&"You stop, something comes into view"));
+        assert!(!game.map().visible_monster_ids().is_empty());
+    }
+
+    #[test]
+    fn resting_stops_when_a_monster_comes_into_view() {
+        let mut game = game(11);
+        edit_player(&mut game, |player| {
+            player.take_damage(29, &mut vec![]);
+        });
+        assert!(game.map().visible_monster_ids().is_empty());
+
+        let events = repeat(&mut game, Command::Rest);
+
+        assert!(texts(&events).contains(&"You stop, something comes into view"));
+        assert!(!game.map().visible_monster_ids().is_empty());
+    }
+
+    #[test]
+    fn exploring_and_resting_stop_when_the_player_is_hurt() {
+        for command in [Command::Explore, Command::Rest] {
+            let mut game = empty_game();
+            poison(&mut game);
+
+            let events = repeat(&mut game, command);
+
+            assert!(took_turn(&events), "{:?}", command);
+            assert_eq!(
+                texts(&events).last(),
+                Some(&"You stop, you are hurt"),
+                "{:?}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn single_steps_are_never_disturbed() {
+        let mut game = empty_game();
+        poison(&mut game);
+
+        let events = game.apply(Command::Wait);
+
+        assert!(took_turn(&events));
+        assert!(!events.iter().any(|event| matches!(event, Event::Disturbed)));
+    }
+
+    #[test]
+    fn waiting_takes_a_turn() {
+        let mut game = empty_game();
+
+        let events = game.apply(Command::Wait);
+
+        assert!(took_turn(&events));
+        assert!(texts(&events).is_empty());
+    }
+
+    #[test]
+    fn resting_at_full_health_is_refused() {
+        let mut game = empty_game();
+
+        let events = game.apply(Command::Rest);
+
+        assert!(!took_turn(&events));
+        assert_eq!(texts(&events), ["You are at full health"]);
+    }
+
+    #[test]
+    fn resting_heals_the_player() {
+        let mut game = empty_game();
+        edit_player(&mut game, |player| {
+            player.take_damage(10, &mut vec![]);
+        });
+        let hp = game.map().player().current_hp();
+
+        for _ in 0..100 {
+            assert!(took_turn(&game.apply(Command::Rest)));
+        }
+
+        assert!(game.map().player().current_hp() > hp);
+    }
+
+    #[test]
+    fn exploring_leads_to_the_stairs_down() {
+        let mut game = empty_game();
+
+        let mut turns = 0;
+        while took_turn(&game.apply(Command::Explore)) {
+            turns += 1;
+            assert!(turns < 5000, "exploring never finished");
+        }
+
+        let stairs = game.map().get_down_stairs();
+        while took_turn(&game.apply(Command::Travel { target: stairs })) {}
+
+        let events = game.apply(Command::Descend);
+
+        assert_eq!(texts(&events), ["You descend to depth 2"]);
+        assert_eq!(game.get_depth(), 2);
+    }
+
+    #[test]
+    fn descending_away_from_the_stairs_does_nothing() {
+        let mut game = empty_game();
+
+        let events = game.apply(Command::Descend);
+
+        assert!(events.is_empty());
+        assert_eq!(game.get_depth(), 1);
+    }
+
+    #[test]
+    fn enough_experience_asks_for_a_level_up() {
+        let mut game = empty_game();
+        edit_player(&mut game, |player| {
+            let xp = player.xp_to_level_up();
+            player.gain_xp(xp, &mut vec![]);
+        });
+
+        let events = game.apply(Command::Wait);
+        assert!(matches!(events.last(), Some(Event::LevelUp)));
+
+        let events = game.apply(Command::LevelUp(Stat::MaxHp));
+        assert_eq!(
+            texts(&events),
+            ["Your battle skills grow stronger! You reached level 2"]
+        );
+        assert!(!events.iter().any(|event| matches!(event, Event::LevelUp)));
+        assert_eq!(game.map().player().level(), 2);
+    }
+
+    #[test]
+    fn a_dead_player_ends_the_game() {
+        let mut game = empty_game();
+        edit_player(&mut game, |player| {
+            let hp = player.current_hp();
+            player.take_damage(hp - 1, &mut vec![]);
+            player.add_effect(StatusEffect::new(EffectKind::Poison, 3, 1), &mut vec![]);
+        });
+
+        let events = game.apply(Command::Wait);
+
+        assert!(matches!(events.last(), Some(Event::PlayerDied)));
+        assert!(game.apply(Command::Wait).is_empty());
+    }
+}
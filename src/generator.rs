@@ -0,0 +1,840 @@
+use std::{cmp::min, collections::VecDeque};
+
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    map::{Map, Room},
+    tile::{Tile, TileKind},
+    unit::Coordinates,
+};
+
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MAX_ROOMS: i32 = 30;
+
+/// random placement gives up after this many rooms in a row that did not fit
+const MAX_ROOM_ATTEMPTS: i32 = 1000;
+
+/// smallest BSP leaf: a room of minimal size plus a wall on each side
+const BSP_MIN_LEAF: i32 = ROOM_MIN_SIZE + 2;
+/// leaves up to this size may randomly stop being split
+const BSP_MAX_LEAF: i32 = 20;
+
+/// features painted into rooms other than the first: the tile, the chance of a room getting it
+/// and the radius of the patch. A room gets at most one of them
+const ROOM_FEATURES: [(TileKind, f64, i32); 5] = [
+    (TileKind::Grass, 0.12, 3),
+    (TileKind::Rubble, 0.08, 2),
+    (TileKind::Water, 0.08, 2),
+    (TileKind::Lava, 0.04, 1),
+    (TileKind::Chasm, 0.04, 1),
+];
+
+/// chance of a cave tile starting out as wall before smoothing
+const CAVE_WALL_CHANCE: f64 = 0.45;
+const CAVE_SMOOTHING_STEPS: i32 = 5;
+/// an open tile turns into wall when at least this many of its eight neighbours are walls
+const CAVE_WALL_BIRTH: i32 = 5;
+/// a wall stays when at least this many of its eight neighbours are walls
+const CAVE_WALL_SURVIVAL: i32 = 4;
+/// the cave is generated again when its largest open area is smaller than this share of the map
+const CAVE_MIN_OPEN_SHARE: f64 = 0.35;
+const CAVE_MAX_ATTEMPTS: i32 = 10;
+/// open chambers carved into the cave to give spawning and the stairs a place
+const CAVE_CHAMBERS: usize = 15;
+/// chambers reach this far from their center in every direction
+const CAVE_CHAMBER_RADIUS: i32 = 2;
+/// chance of a lava river crossing a cave
+const CAVE_RIVER_CHANCE: f64 = 0.5;
+const CAVE_MAX_LAKES: i32 = 3;
+
+/// carves rooms and the corridors between them into the tiles of a map
+pub trait Generator {
+    /// returns the carved rooms, the first one is where the player arrives; at least one room
+    /// must be returned
+    fn generate(&self, map: &mut Map, rng: &mut ChaCha12Rng) -> Vec<Room>;
+}
+
+/// which generator builds the levels of a game
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GeneratorKind {
+    RandomRooms,
+    Bsp,
+    Caves,
+}
+
+impl GeneratorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rooms" => Some(GeneratorKind::RandomRooms),
+            "bsp" => Some(GeneratorKind::Bsp),
+            "caves" => Some(GeneratorKind::Caves),
+            _ => None,
+        }
+    }
+
+    pub fn generator(self) -> Box<dyn Generator> {
+        match self {
+            GeneratorKind::RandomRooms => Box::new(RandomRooms),
+            GeneratorKind::Bsp => Box::new(Bsp),
+            GeneratorKind::Caves => Box::new(Caves),
+        }
+    }
+}
+
+fn h_v_tunnel(new_center: &Coordinates, prev_center: &Coordinates, map: &mut Map) {
+    let (bottom_y, top_y) = (prev_center.y, prev_center.y + 1);
+    let (left_x, right_x) = if new_center.x < prev_center.x {
+        (new_center.x, prev_center.x)
+    } else {
+        (prev_center.x, new_center.x)
+    };
+
+    let h_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
+    h_tunnel.fill(map);
+
+    let (left_x, right_x) = (new_center.x, new_center.x + 1);
+    let (bottom_y, top_y) = if new_center.y < prev_center.y {
+        (new_center.y, prev_center.y)
+    } else {
+        (prev_center.y, new_center.y)
+    };
+
+    let v_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
+    v_tunnel.fill(map);
+
+    if !h_tunnel.intersects_with_as_tunnels(&v_tunnel) {
+        let v_tunnel = Room::new(left_x, right_x, bottom_y, top_y + 1);
+        v_tunnel.fill(map);
+    }
+}
+
+fn v_h_tunnel(new_center: &Coordinates, prev_center: &Coordinates, map: &mut Map) {
+    let (left_x, right_x) = (prev_center.x, prev_center.x + 1);
+    let (bottom_y, top_y) = if new_center.y < prev_center.y {
+        (new_center.y, prev_center.y)
+    } else {
+        (prev_center.y, new_center.y)
+    };
+
+    let v_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
+    v_tunnel.fill(map);
+
+    let (bottom_y, top_y) = (new_center.y, new_center.y + 1);
+    let (left_x, right_x) = if new_center.x < prev_center.x {
+        (new_center.x, prev_center.x)
+    } else {
+        (prev_center.x, new_center.x)
+    };
+
+    let h_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
+    h_tunnel.fill(map);
+
+    if !h_tunnel.intersects_with_as_tunnels(&v_tunnel) {
+        let h_tunnel = Room::new(left_x, right_x + 1, bottom_y, top_y);
+        h_tunnel.fill(map);
+    }
+}
+
+/// squared distance between the centers of two rooms
+fn distance_between(room1: &Room, room2: &Room) -> i32 {
+    let (center1, center2) = (room1.get_center(), room2.get_center());
+
+    (center1.x - center2.x).pow(2) + (center1.y - center2.y).pow(2)
+}
+
+fn find_nearest_room<'a>(rooms: &'a [Room], target_room: &Room) -> &'a Room {
+    rooms
+        .iter()
+        .min_by_key(|room| distance_between(target_room, room))
+        .expect("there is no room to connect to")
+}
+
+/// put closed doors where tunnels enter rooms: on the tiles just outside a room's edge that
+/// are open but have walls on both sides along that edge
+fn place_doors(map: &mut Map, rooms: &[Room]) {
+    let is_wall = |map: &Map, x: i32, y: i32| map.get_tile(x, y).map_or(true, Tile::is_blocked);
+
+    for room in rooms {
+        let mut doorways = vec![];
+
+        for x in room.left_x..room.right_x {
+            for y in [room.bottom_y - 1, room.top_y] {
+                if !is_wall(map, x, y) && is_wall(map, x - 1, y) && is_wall(map, x + 1, y) {
+                    doorways.push((x, y));
+                }
+            }
+        }
+
+        for y in room.bottom_y..room.top_y {
+            for x in [room.left_x - 1, room.right_x] {
+                if !is_wall(map, x, y) && is_wall(map, x, y - 1) && is_wall(map, x, y + 1) {
+                    doorways.push((x, y));
+                }
+            }
+        }
+
+        for (x, y) in doorways {
+            map.set_tile(x, y, Tile::door());
+        }
+    }
+}
+
+fn is_room_center(rooms: &[Room], x: i32, y: i32) -> bool {
+    rooms
+        .iter()
+        .any(|room| room.get_center().is_equal(&Coordinates { x, y }))
+}
+
+/// turn the floor around `center` into a ragged patch of `kind`, only where `is_allowed`.
+/// Room centers are left alone, the player arrives and the stairs are placed there
+fn paint_blob(
+    map: &mut Map,
+    rooms: &[Room],
+    (center, radius): (Coordinates, i32),
+    kind: TileKind,
+    rng: &mut ChaCha12Rng,
+    is_allowed: impl Fn(i32, i32) -> bool,
+) {
+    let (width, height) = (map.get_width(), map.get_height());
+
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            let (x, y) = (center.x + dx, center.y + dy);
+
+            // the border of the map stays wall
+            if x < 1 || x >= width - 1 || y < 1 || y >= height - 1 {
+                continue;
+            }
+
+            let reach = (radius.pow(2) as f64 * rng.gen_range(0.5..1.3)) as i32;
+
+            if dx.pow(2) + dy.pow(2) > reach || !is_allowed(x, y) || is_room_center(rooms, x, y) {
+                continue;
+            }
+
+            if map
+                .get_tile(x, y)
+                .is_ok_and(|tile| tile.kind() == TileKind::Floor)
+            {
+                map.set_tile(x, y, Tile::new(kind));
+            }
+        }
+    }
+}
+
+/// give some rooms a patch of grass, rubble, water, lava or a chasm
+fn paint_room_features(map: &mut Map, rooms: &[Room], rng: &mut ChaCha12Rng) {
+    for room in rooms.iter().skip(1) {
+        let Some(&(kind, _, radius)) = ROOM_FEATURES
+            .iter()
+            .find(|(_, chance, _)| rng.gen_bool(*chance))
+        else {
+            continue;
+        };
+
+        let center = Coordinates {
+            x: rng.gen_range(room.left_x..room.right_x),
+            y: rng.gen_range(room.bottom_y..room.top_y),
+        };
+
+        paint_blob(map, rooms, (center, radius), kind, rng, |x, y| {
+            room.contains(x, y)
+        });
+    }
+}
+
+/// a river of `kind` meandering from the top of the map to the bottom, two tiles wide.
+/// It floods the floor it crosses but leaves the rooms alone
+fn paint_river(map: &mut Map, rooms: &[Room], kind: TileKind, rng: &mut ChaCha12Rng) {
+    let (width, height) = (map.get_width(), map.get_height());
+
+    if width < 8 {
+        return;
+    }
+
+    let mut x = rng.gen_range(width / 4..width * 3 / 4);
+
+    for y in 1..height - 1 {
+        for river_x in [x, x + 1] {
+            let is_in_room = rooms.iter().any(|room| room.contains(river_x, y));
+            let is_floor = map
+                .get_tile(river_x, y)
+                .is_ok_and(|tile| tile.kind() == TileKind::Floor);
+
+            if is_floor && !is_in_room {
+                map.set_tile(river_x, y, Tile::new(kind));
+            }
+        }
+
+        x = (x + rng.gen_range(-1..=1)).clamp(1, width - 3);
+    }
+}
+
+/// steps from `start` to every open tile it connects to, `None` for walls and cut off tiles
+fn flood(walls: &[bool], (width, height): (i32, i32), start: i32) -> Vec<Option<i32>> {
+    let mut distance = vec![None; walls.len()];
+    let mut queue = VecDeque::new();
+
+    distance[start as usize] = Some(0);
+    queue.push_back(start);
+
+    while let Some(index) = queue.pop_front() {
+        let (x, y) = (index % width, index / width);
+        let steps = distance[index as usize].unwrap_or(0);
+
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let (next_x, next_y) = (x + dx, y + dy);
+
+            if next_x < 0 || next_x >= width || next_y < 0 || next_y >= height {
+                continue;
+            }
+
+            let next = next_x + next_y * width;
+
+            if !walls[next as usize] && distance[next as usize].is_none() {
+                distance[next as usize] = Some(steps + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    distance
+}
+
+/// which parts of a generated map can be walked to from the first room
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConnectivityReport {
+    pub reachable_tiles: usize,
+    /// walkable tiles that cannot be reached, like pockets left over between rooms
+    pub unreachable_tiles: usize,
+    /// indices of the rooms whose center cannot be reached
+    pub unreachable_rooms: Vec<usize>,
+}
+
+impl ConnectivityReport {
+    pub fn is_connected(&self) -> bool {
+        self.unreachable_rooms.is_empty()
+    }
+}
+
+/// flood fill over the safe walkable tiles and doors of the map, starting from the center of the first room
+pub fn check_connectivity(map: &Map, rooms: &[Room]) -> ConnectivityReport {
+    let (width, height) = (map.get_width(), map.get_height());
+
+    let mut walls = vec![true; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            // doors can be opened, so they do not cut anything off, while nobody should have to
+            // cross lava or a chasm to get somewhere
+            walls[(x + y * width) as usize] = map.get_tile(x, y).map_or(true, |tile| {
+                (tile.is_blocked() && !tile.is_door()) || tile.kind().is_hazard()
+            });
+        }
+    }
+
+    let Some(first) = rooms.first() else {
+        return ConnectivityReport {
+            reachable_tiles: 0,
+            unreachable_tiles: walls.iter().filter(|wall| !**wall).count(),
+            unreachable_rooms: vec![],
+        };
+    };
+
+    let start = first.get_center();
+    let distance = flood(&walls, (width, height), start.x + start.y * width);
+
+    let reachable_tiles = distance.iter().filter(|steps| steps.is_some()).count();
+    let unreachable_tiles = walls.iter().filter(|wall| !**wall).count() - reachable_tiles;
+
+    let unreachable_rooms = rooms
+        .iter()
+        .enumerate()
+        .filter(|(_, room)| {
+            let center = room.get_center();
+
+            distance[(center.x + center.y * width) as usize].is_none()
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    ConnectivityReport {
+        reachable_tiles,
+        unreachable_tiles,
+        unreachable_rooms,
+    }
+}
+
+/// dig tunnels from every unreachable room to the nearest reachable one until all rooms are
+/// connected, returns how many tunnels were needed. Tunnels turn any lava or chasm they cross
+/// back into floor
+pub fn repair_connectivity(map: &mut Map, rooms: &[Room], rng: &mut ChaCha12Rng) -> usize {
+    let mut tunnels = 0;
+
+    // every tunnel connects at least one more room, so this always terminates
+    for _ in 0..rooms.len() {
+        let report = check_connectivity(map, rooms);
+
+        let Some(&unreachable) = report.unreachable_rooms.first() else {
+            break;
+        };
+
+        let nearest = (0..rooms.len())
+            .filter(|index| !report.unreachable_rooms.contains(index))
+            .min_by_key(|index| distance_between(&rooms[unreachable], &rooms[*index]))
+            .expect("the first room is always reachable");
+
+        let (from, to) = (rooms[unreachable].get_center(), rooms[nearest].get_center());
+
+        if rng.gen() {
+            v_h_tunnel(&from, &to, map);
+        } else {
+            h_v_tunnel(&from, &to, map);
+        }
+
+        tunnels += 1;
+    }
+
+    tunnels
+}
+
+/// throws rooms of random size at random places until `MAX_ROOMS` fit, connecting each new room
+/// to a previous one
+pub struct RandomRooms;
+
+impl Generator for RandomRooms {
+    fn generate(&self, map: &mut Map, rng: &mut ChaCha12Rng) -> Vec<Room> {
+        let mut index = 0;
+        let mut attempts = 0;
+        let mut rooms: Vec<Room> = vec![];
+
+        // rooms keep a wall on every side and never grow past what the map can hold
+        let max_w = min(ROOM_MAX_SIZE, map.get_width() - 3);
+        let max_h = min(ROOM_MAX_SIZE, map.get_height() - 3);
+
+        if max_w < ROOM_MIN_SIZE || max_h < ROOM_MIN_SIZE {
+            return rooms;
+        }
+
+        while index < MAX_ROOMS && attempts < MAX_ROOM_ATTEMPTS {
+            let w = rng.gen_range(ROOM_MIN_SIZE..max_w + 1);
+            let h = rng.gen_range(ROOM_MIN_SIZE..max_h + 1);
+            let y = rng.gen_range(1..map.get_height() - h - 1);
+            let x = rng.gen_range(1..map.get_width() - w - 1);
+
+            let new_room = Room::new(x, x + w, y, y + h);
+
+            let intersects = rooms
+                .iter()
+                .any(|room| room.intersects_with_as_rooms(&new_room));
+
+            if intersects {
+                attempts += 1;
+
+                continue;
+            }
+
+            if !rooms.is_empty() {
+                let nearest = find_nearest_room(&rooms, &new_room);
+
+                if rng.gen() {
+                    v_h_tunnel(&new_room.get_center(), &nearest.get_center(), map);
+                } else {
+                    h_v_tunnel(&new_room.get_center(), &nearest.get_center(), map);
+                }
+            }
+
+            new_room.fill(map);
+
+            rooms.push(new_room);
+
+            index += 1;
+            attempts = 0;
+        }
+
+        place_doors(map, &rooms);
+        paint_room_features(map, &rooms, rng);
+
+        rooms
+    }
+}
+
+/// binary space partition: the map is split recursively into leaves, each leaf gets a room and
+/// the two halves of every split are connected by a tunnel
+pub struct Bsp;
+
+impl Bsp {
+    /// build the subtree of the area with the given corner and size,
+    /// returning the center of one of its rooms for the parent to connect to
+    fn build(
+        &self,
+        (x, y): (i32, i32),
+        (w, h): (i32, i32),
+        map: &mut Map,
+        rng: &mut ChaCha12Rng,
+        rooms: &mut Vec<Room>,
+    ) -> Coordinates {
+        let can_cut_vertically = w >= BSP_MIN_LEAF * 2;
+        let can_cut_horizontally = h >= BSP_MIN_LEAF * 2;
+        let is_small = w <= BSP_MAX_LEAF && h <= BSP_MAX_LEAF;
+
+        if (!can_cut_vertically && !can_cut_horizontally) || (is_small && rng.gen()) {
+            let room_w = rng.gen_range(ROOM_MIN_SIZE..=min(ROOM_MAX_SIZE, w - 2));
+            let room_h = rng.gen_range(ROOM_MIN_SIZE..=min(ROOM_MAX_SIZE, h - 2));
+            let room_x = rng.gen_range(x + 1..=x + w - 1 - room_w);
+            let room_y = rng.gen_range(y + 1..=y + h - 1 - room_h);
+
+            let room = Room::new(room_x, room_x + room_w, room_y, room_y + room_h);
+            room.fill(map);
+
+            let center = room.get_center();
+            rooms.push(room);
+
+            return center;
+        }
+
+        // cut across the longer side so that leaves stay roughly square
+        let cut_vertically = match (can_cut_vertically, can_cut_horizontally) {
+            (true, false) => true,
+            (false, true) => false,
+            _ if w * 4 > h * 5 => true,
+            _ if h * 4 > w * 5 => false,
+            _ => rng.gen(),
+        };
+
+        let (first, second) = if cut_vertically {
+            let cut = rng.gen_range(BSP_MIN_LEAF..=w - BSP_MIN_LEAF);
+
+            (
+                self.build((x, y), (cut, h), map, rng, rooms),
+                self.build((x + cut, y), (w - cut, h), map, rng, rooms),
+            )
+        } else {
+            let cut = rng.gen_range(BSP_MIN_LEAF..=h - BSP_MIN_LEAF);
+
+            (
+                self.build((x, y), (w, cut), map, rng, rooms),
+                self.build((x, y + cut), (w, h - cut), map, rng, rooms),
+            )
+        };
+
+        if rng.gen() {
+            v_h_tunnel(&second, &first, map);
+        } else {
+            h_v_tunnel(&second, &first, map);
+        }
+
+        if rng.gen() {
+            first
+        } else {
+            second
+        }
+    }
+}
+
+impl Generator for Bsp {
+    fn generate(&self, map: &mut Map, rng: &mut ChaCha12Rng) -> Vec<Room> {
+        let mut rooms = vec![];
+
+        if map.get_width() < BSP_MIN_LEAF || map.get_height() < BSP_MIN_LEAF {
+            return rooms;
+        }
+
+        let size = (map.get_width(), map.get_height());
+
+        self.build((0, 0), size, map, rng, &mut rooms);
+
+        place_doors(map, &rooms);
+        paint_room_features(map, &rooms, rng);
+
+        rooms
+    }
+}
+
+/// organic caves by cellular automata: random noise is smoothed until walls clump together,
+/// then every pocket not connected to the largest open area is filled in
+pub struct Caves;
+
+impl Caves {
+    fn noise(&self, (width, height): (i32, i32), rng: &mut ChaCha12Rng) -> Vec<bool> {
+        let mut walls = vec![true; (width * height) as usize];
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                walls[(x + y * width) as usize] = rng.gen_bool(CAVE_WALL_CHANCE);
+            }
+        }
+
+        walls
+    }
+
+    /// one automaton step, tiles off the map count as walls so that the border stays closed
+    fn smooth(&self, walls: &[bool], (width, height): (i32, i32)) -> Vec<bool> {
+        let is_wall = |x: i32, y: i32| {
+            x < 0 || x >= width || y < 0 || y >= height || walls[(x + y * width) as usize]
+        };
+
+        let mut smoothed = vec![true; walls.len()];
+
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let mut neighbours = 0;
+
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        if (dx != 0 || dy != 0) && is_wall(x + dx, y + dy) {
+                            neighbours += 1;
+                        }
+                    }
+                }
+
+                smoothed[(x + y * width) as usize] = if is_wall(x, y) {
+                    neighbours >= CAVE_WALL_SURVIVAL
+                } else {
+                    neighbours >= CAVE_WALL_BIRTH
+                };
+            }
+        }
+
+        smoothed
+    }
+
+    /// the largest connected open area, as a mask over the grid
+    fn largest_area(&self, walls: &[bool], size: (i32, i32)) -> Vec<bool> {
+        let mut seen = vec![false; walls.len()];
+        let mut largest = vec![false; walls.len()];
+        let mut largest_size = 0;
+
+        for start in 0..walls.len() {
+            if walls[start] || seen[start] {
+                continue;
+            }
+
+            let area: Vec<bool> = flood(walls, size, start as i32)
+                .iter()
+                .map(Option::is_some)
+                .collect();
+            let area_size = area.iter().filter(|open| **open).count();
+
+            for (index, open) in area.iter().enumerate() {
+                seen[index] |= *open;
+            }
+
+            if area_size > largest_size {
+                largest_size = area_size;
+                largest = area;
+            }
+        }
+
+        largest
+    }
+}
+
+impl Generator for Caves {
+    fn generate(&self, map: &mut Map, rng: &mut ChaCha12Rng) -> Vec<Room> {
+        let (width, height) = (map.get_width(), map.get_height());
+        let size = (width, height);
+        let min_open = ((width * height) as f64 * CAVE_MIN_OPEN_SHARE) as usize;
+
+        if width < 3 || height < 3 {
+            return vec![];
+        }
+
+        let mut open = vec![];
+        let mut open_size = 0;
+
+        for _ in 0..CAVE_MAX_ATTEMPTS {
+            let mut walls = self.noise(size, rng);
+
+            for _ in 0..CAVE_SMOOTHING_STEPS {
+                walls = self.smooth(&walls, size);
+            }
+
+            let area = self.largest_area(&walls, size);
+            let area_size = area.iter().filter(|open| **open).count();
+
+            if area_size > open_size {
+                open = area;
+                open_size = area_size;
+            }
+
+            if open_size >= min_open {
+                break;
+            }
+        }
+
+        let cells: Vec<i32> = (0..open.len() as i32)
+            .filter(|index| open[*index as usize])
+            .collect();
+
+        if cells.is_empty() {
+            return vec![];
+        }
+
+        // chambers are carved around open tiles of the cave, so each of them is connected to it;
+        // they never overlap so that nothing spawns twice on the same tile
+        let mut rooms: Vec<Room> = vec![];
+
+        for _ in 0..CAVE_CHAMBERS * CAVE_MAX_ATTEMPTS as usize {
+            if rooms.len() == CAVE_CHAMBERS {
+                break;
+            }
+
+            let index = cells[rng.gen_range(0..cells.len())];
+            let (x, y) = (index % width, index / width);
+
+            let chamber = Room::new(
+                (x - CAVE_CHAMBER_RADIUS).max(1),
+                (x + CAVE_CHAMBER_RADIUS + 1).min(width - 1),
+                (y - CAVE_CHAMBER_RADIUS).max(1),
+                (y + CAVE_CHAMBER_RADIUS + 1).min(height - 1),
+            );
+
+            if !rooms
+                .iter()
+                .any(|room| room.intersects_with_as_rooms(&chamber))
+            {
+                rooms.push(chamber);
+            }
+        }
+
+        let mut walls: Vec<bool> = open.iter().map(|open| !open).collect();
+
+        for room in &rooms {
+            for x in room.left_x..room.right_x {
+                for y in room.bottom_y..room.top_y {
+                    walls[(x + y * width) as usize] = false;
+                }
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                if !walls[(x + y * width) as usize] {
+                    map.set_tile(x, y, Tile::empty());
+                }
+            }
+        }
+
+        // lakes of water and sometimes a river of lava, kept out of the chambers
+        for _ in 0..rng.gen_range(0..=CAVE_MAX_LAKES) {
+            let index = cells[rng.gen_range(0..cells.len())];
+            let center = Coordinates {
+                x: index % width,
+                y: index / width,
+            };
+            let radius = rng.gen_range(3..=6);
+
+            paint_blob(
+                map,
+                &rooms,
+                (center, radius),
+                TileKind::Water,
+                rng,
+                |x, y| !rooms.iter().any(|room| room.contains(x, y)),
+            );
+        }
+
+        if rng.gen_bool(CAVE_RIVER_CHANCE) {
+            paint_river(map, &rooms, TileKind::Lava, rng);
+        }
+
+        // the stairs go into the chamber farthest from where the player arrives
+        let start = rooms[0].get_center();
+        let distance = flood(&walls, size, start.x + start.y * width);
+        let steps_to = |room: &Room| {
+            let center = room.get_center();
+
+            distance[(center.x + center.y * width) as usize].unwrap_or(0)
+        };
+
+        let farthest = (1..rooms.len()).max_by_key(|index| steps_to(&rooms[*index]));
+
+        if let Some(farthest) = farthest {
+            let last = rooms.len() - 1;
+            rooms.swap(farthest, last);
+        }
+
+        rooms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::monsters::MonsterTemplates;
+
+    /// a map of walls only, for the generators and checks to work on
+    fn blank_map(width: i32, height: i32) -> Map {
+        let mut map = Map::new(
+            width,
+            height,
+            0,
+            1,
+            &MonsterTemplates::default(),
+            &RandomRooms,
+        );
+
+        for x in 0..width {
+            for y in 0..height {
+                map.set_tile(x, y, Tile::wall());
+            }
+        }
+
+        map
+    }
+
+    #[test]
+    fn every_generator_builds_connected_maps() {
+        for kind in [
+            GeneratorKind::RandomRooms,
+            GeneratorKind::Bsp,
+            GeneratorKind::Caves,
+        ] {
+            for seed in 0..10 {
+                let mut map = blank_map(80, 50);
+                let mut rng = ChaCha12Rng::seed_from_u64(seed);
+
+                let rooms = kind.generator().generate(&mut map, &mut rng);
+                repair_connectivity(&mut map, &rooms, &mut rng);
+                let report = check_connectivity(&map, &rooms);
+
+                assert!(!rooms.is_empty(), "{:?} seed {}", kind, seed);
+                assert!(
+                    report.is_connected(),
+                    "{:?} seed {}: {:?}",
+                    kind,
+                    seed,
+                    report
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn separate_rooms_are_flagged_and_repaired() {
+        let mut map = blank_map(40, 30);
+        let rooms = [Room::new(2, 8, 2, 8), Room::new(20, 26, 15, 21)];
+
+        for room in &rooms {
+            room.fill(&mut map);
+        }
+
+        let report = check_connectivity(&map, &rooms);
+
+        assert!(!report.is_connected());
+        assert_eq!(report.unreachable_rooms, [1]);
+        assert_eq!(report.reachable_tiles, 36);
+        assert_eq!(report.unreachable_tiles, 36);
+
+        let mut rng = ChaCha12Rng::seed_from_u64(0);
+
+        assert_eq!(repair_connectivity(&mut map, &rooms, &mut rng), 1);
+        assert!(check_connectivity(&map, &rooms).is_connected());
+    }
+}
@@ -1,9 +1,61 @@
-use tcod::{console::Offscreen, Console};
+use tcod::{
+    colors,
+    console::{blit, Offscreen, Root},
+    input::{Key, KeyCode},
+    BackgroundFlag, Color, Console, TextAlignment,
+};
+
+use crate::{
+    color,
+    game::{Game, Message},
+    item::Item,
+    keys::{Action, KeyBinding, KeyBindings},
+    render::Renderer,
+    unit::{Stat, Unit, LEVEL_UP_DAMAGE, LEVEL_UP_DEFENSE, LEVEL_UP_MAX_HP},
+};
+
+/// row of the status effects on the player, under the status line
+const EFFECTS_Y: i32 = 2;
+
+const LOG_X: i32 = 1;
+const LOG_Y: i32 = 4;
+
+const POPUP_WIDTH: i32 = 50;
+
+/// draw messages from the bottom of the given area upwards, skipping the `scroll` newest ones,
+/// and wrapping lines longer than `width`
+fn draw_messages<C: Console>(
+    con: &mut C,
+    messages: &[Message],
+    scroll: usize,
+    (x, top_y): (i32, i32),
+    (width, height): (i32, i32),
+) {
+    let mut y = top_y + height;
+
+    for message in messages.iter().rev().skip(scroll) {
+        let message_height = con.get_height_rect(x, top_y, width, 0, &message.text);
+        y -= message_height;
+
+        if y < top_y {
+            break;
+        }
+
+        con.set_default_foreground(message.color.into());
+        con.print_rect(x, y, width, 0, &message.text);
+    }
+}
 
 pub struct GUI {
     offscreen: Offscreen,
     width: i32,
     height: i32,
+    messages: Vec<Message>,
+    scroll: usize,
+    /// description of the tile under the look cursor, shown instead of the log while looking
+    description: Option<Vec<Message>>,
+    /// names of what is under the mouse
+    hover: Option<String>,
 }
 
 impl GUI {
@@ -12,6 +64,362 @@ impl GUI {
             offscreen: Offscreen::new(width, height),
             width,
             height,
+            messages: vec![],
+            scroll: 0,
+            description: None,
+            hover: None,
         }
     }
+
+    /// show the names of what is under the mouse above the log, or nothing for `None`
+    pub fn set_hover(&mut self, hover: Option<String>) {
+        self.hover = hover;
+    }
+
+    /// show the lines in place of the message log until it is called again with `None`
+    pub fn set_description(&mut self, description: Option<Vec<Message>>) {
+        self.description = description;
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
+        self.scroll = 0;
+    }
+
+    /// scroll the log towards older (positive `by`) or newer (negative `by`) messages
+    pub fn scroll_log(&mut self, by: i32) {
+        let max_scroll = self.messages.len().saturating_sub(1) as i32;
+
+        self.scroll = (self.scroll as i32 + by).clamp(0, max_scroll) as usize;
+    }
+
+    /// draw the status line and the message log into the panel and blit it to `root` at row `y`
+    pub fn render(&mut self, root: &mut Root, game: &Game, y: i32) {
+        self.offscreen.set_default_background(colors::BLACK);
+        self.offscreen.clear();
+
+        let player = game.map().player();
+
+        self.offscreen.set_default_foreground(colors::WHITE);
+        self.offscreen.print_ex(
+            1,
+            1,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            format!("HP: {}/{}", player.current_hp(), player.max_hp()),
+        );
+        self.offscreen.print_ex(
+            16,
+            1,
+            BackgroundFlag::None,
+            TextAlignment::Left,
+            format!(
+                "Level: {}  XP: {}/{}",
+                player.level(),
+                player.xp(),
+                player.xp_to_level_up()
+            ),
+        );
+        self.offscreen.print_ex(
+            self.width / 2,
+            1,
+            BackgroundFlag::None,
+            TextAlignment::Center,
+            format!("Depth: {}", game.get_depth()),
+        );
+        self.offscreen.print_ex(
+            self.width - 2,
+            1,
+            BackgroundFlag::None,
+            TextAlignment::Right,
+            format!("Seed: {}", game.get_seed()),
+        );
+
+        let mut effect_x = 1;
+
+        for effect in player.effects() {
+            let (icon, color) = effect.icon();
+            let label = format!("{} {}", effect.label(), effect.turns);
+
+            Renderer::put_char(&mut self.offscreen, effect_x, EFFECTS_Y, icon, color);
+            self.offscreen.set_default_foreground(colors::WHITE);
+            self.offscreen.print_ex(
+                effect_x + 2,
+                EFFECTS_Y,
+                BackgroundFlag::None,
+                TextAlignment::Left,
+                &label,
+            );
+
+            effect_x += label.len() as i32 + 4;
+        }
+
+        if let Some(hover) = &self.hover {
+            self.offscreen.set_default_foreground(colors::LIGHT_CYAN);
+            self.offscreen.print_ex(
+                LOG_X,
+                LOG_Y - 1,
+                BackgroundFlag::None,
+                TextAlignment::Left,
+                hover,
+            );
+        }
+
+        if let Some(description) = &self.description {
+            let (x, width) = (LOG_X, self.width - LOG_X * 2);
+            let mut line_y = LOG_Y;
+
+            for line in description {
+                self.offscreen.set_default_foreground(line.color.into());
+                self.offscreen.print_rect(x, line_y, width, 0, &line.text);
+
+                line_y += self
+                    .offscreen
+                    .get_height_rect(x, line_y, width, 0, &line.text);
+            }
+        } else {
+            if self.scroll > 0 {
+                self.offscreen.set_default_foreground(colors::LIGHT_GREY);
+                self.offscreen.print_ex(
+                    self.width - 2,
+                    LOG_Y - 1,
+                    BackgroundFlag::None,
+                    TextAlignment::Right,
+                    format!("-- {} newer --", self.scroll),
+                );
+            }
+
+            draw_messages(
+                &mut self.offscreen,
+                &self.messages,
+                self.scroll,
+                (LOG_X, LOG_Y),
+                (self.width - LOG_X * 2, self.height - LOG_Y - 1),
+            );
+        }
+
+        blit(
+            &self.offscreen,
+            (0, 0),
+            (self.width, self.height),
+            root,
+            (0, y),
+            1.0,
+            1.0,
+        );
+    }
+
+    /// full-screen message history; arrows and page keys scroll, Escape or the keys bound to
+    /// `Action::History` close it
+    pub fn show_history(&self, root: &mut Root, keys: &KeyBindings) {
+        let page = root.height() / 2;
+        let max_scroll = self.messages.len().saturating_sub(1) as i32;
+        let mut scroll = 0;
+
+        loop {
+            root.set_default_background(colors::BLACK);
+            root.clear();
+
+            root.set_default_foreground(colors::WHITE);
+            root.print_ex(
+                root.width() / 2,
+                1,
+                BackgroundFlag::None,
+                TextAlignment::Center,
+                "Message history (arrows / PgUp / PgDn to scroll, Esc to close)",
+            );
+
+            let (width, height) = (root.width(), root.height());
+
+            draw_messages(
+                root,
+                &self.messages,
+                scroll as usize,
+                (LOG_X, LOG_Y),
+                (width - LOG_X * 2, height - LOG_Y - 1),
+            );
+
+            root.flush();
+
+            let key = root.wait_for_keypress(true);
+
+            scroll = match (key.code, key.printable) {
+                (KeyCode::Up, _) => scroll + 1,
+                (KeyCode::Down, _) => scroll - 1,
+                (KeyCode::PageUp, _) => scroll + page,
+                (KeyCode::PageDown, _) => scroll - page,
+                (KeyCode::Escape, _) => break,
+                _ if keys.action(&key) == Some(Action::History) => break,
+                _ => scroll,
+            }
+            .clamp(0, max_scroll);
+
+            if root.window_closed() {
+                break;
+            }
+        }
+    }
+
+    /// show a menu over the map listing the items with a letter each,
+    /// returning the index of the chosen item or `None` if the menu was cancelled
+    pub fn inventory_menu(&self, root: &mut Root, header: &str, items: &[Item]) -> Option<usize> {
+        let mut text = String::from(header);
+
+        if items.is_empty() {
+            text.push_str("\nNothing here.");
+        }
+
+        for (index, item) in items.iter().enumerate() {
+            let letter = (b'a' + index as u8) as char;
+
+            text.push_str(&format!("\n({}) {}", letter, item.label()));
+        }
+
+        let key = show_popup(root, &text);
+
+        if key.code != KeyCode::Char || !key.printable.is_ascii_lowercase() {
+            return None;
+        }
+
+        let index = (key.printable as u8 - b'a') as usize;
+
+        if index < items.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    /// show the effective stats of the player, with the bonuses from the worn equipment
+    pub fn show_character(&self, root: &mut Root, player: &Unit) {
+        let mut text = format!(
+            "Character\n\nLevel: {}\nExperience: {}/{}\n\nHP: {}/{} (base {})\nDamage: {} (base {})\nDefense: {} (base {})\nSpeed: {}\n\nEquipment:",
+            player.level(),
+            player.xp(),
+            player.xp_to_level_up(),
+            player.current_hp(),
+            player.max_hp(),
+            player.base_max_hp(),
+            player.damage(),
+            player.base_damage(),
+            player.defense(),
+            player.base_defense(),
+            player.speed(),
+        );
+
+        if player.equipment().is_empty() {
+            text.push_str("\n  nothing");
+        }
+
+        for item in player.equipment() {
+            text.push_str(&format!("\n  {}", item.label()));
+        }
+
+        text.push_str("\n\nPress any key to continue.");
+
+        show_popup(root, &text);
+    }
+
+    /// list every action with the keys bound to it
+    pub fn show_help(&self, root: &mut Root, keys: &KeyBindings) {
+        let mut text = String::from("Keys\n");
+
+        for action in Action::ALL {
+            let bound: Vec<String> = keys
+                .keys(action)
+                .iter()
+                .map(KeyBinding::to_string)
+                .collect();
+            let bound = if bound.is_empty() {
+                String::from("-")
+            } else {
+                bound.join(", ")
+            };
+
+            text.push_str(&format!("\n{:<16}{}", bound, action.description()));
+        }
+
+        text.push_str(
+            "\n\nPoint the mouse at a tile to name what is there, click to travel to it.\n\nPress any key to continue.",
+        );
+
+        show_popup(root, &text);
+    }
+
+    /// ask which stat to raise for the new level; the menu stays open until one is chosen
+    pub fn level_up_menu(&self, root: &mut Root, player: &Unit) -> Stat {
+        let text = format!(
+            "You feel more experienced. Choose a stat to raise:\n\n(a) Constitution: +{} HP, from {}\n(b) Strength: +{} damage, from {}\n(c) Agility: +{} defense, from {}",
+            LEVEL_UP_MAX_HP,
+            player.base_max_hp(),
+            LEVEL_UP_DAMAGE,
+            player.base_damage(),
+            LEVEL_UP_DEFENSE,
+            player.base_defense(),
+        );
+
+        loop {
+            let key = show_popup(root, &text);
+
+            match (key.code, key.printable) {
+                (KeyCode::Char, 'a') => return Stat::MaxHp,
+                (KeyCode::Char, 'b') => return Stat::Damage,
+                (KeyCode::Char, 'c') => return Stat::Defense,
+                _ if root.window_closed() => return Stat::MaxHp,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// draw the text in a window over the center of the screen and wait for a key
+fn show_popup(root: &mut Root, text: &str) -> Key {
+    let height = root.get_height_rect(0, 0, POPUP_WIDTH, root.height(), text);
+
+    let mut window = Offscreen::new(POPUP_WIDTH, height);
+
+    window.set_default_foreground(colors::WHITE);
+    window.print_rect_ex(
+        0,
+        0,
+        POPUP_WIDTH,
+        height,
+        BackgroundFlag::None,
+        TextAlignment::Left,
+        text,
+    );
+
+    let x = (root.width() - POPUP_WIDTH) / 2;
+    let y = (root.height() - height) / 2;
+
+    blit(
+        &window,
+        (0, 0),
+        (POPUP_WIDTH, height),
+        root,
+        (x, y),
+        1.0,
+        0.7,
+    );
+
+    root.flush();
+
+    root.wait_for_keypress(true)
+}
+
+impl From<color::Color> for Color {
+    fn from(color: color::Color) -> Self {
+        Color::new(color.r, color.g, color.b)
+    }
+}
+
+impl Renderer for Offscreen {
+    fn put_char(&mut self, x: i32, y: i32, char: char, color: color::Color) {
+        self.set_default_foreground(color.into());
+        Console::put_char(self, x, y, char, BackgroundFlag::None);
+    }
+
+    fn set_background(&mut self, x: i32, y: i32, color: color::Color) {
+        self.set_char_background(x, y, color.into(), BackgroundFlag::Set);
+    }
 }
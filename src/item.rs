@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::{self, Color},
+    effect::{EffectKind, StatusEffect},
+    render::Renderer,
+    unit::Coordinates,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ItemKind {
+    /// restores `heal` hit points of whoever drinks it
+    Potion { heal: i32 },
+    /// puts its effect on whoever drinks it
+    Elixir(StatusEffect),
+    /// strikes the closest visible monster within `range` for `damage`
+    Scroll { damage: i32, range: i32 },
+    /// a bolt cast at a chosen target, it hits the first unit in its way for `damage`
+    Spell { damage: i32, range: i32 },
+    /// a bolt cast at a chosen target, it puts `effect` on the first unit in its way
+    Curse { effect: StatusEffect, range: i32 },
+    /// thrown at a chosen target, hitting the first unit in its way, and left where it lands
+    Thrown { damage: i32, range: i32 },
+    /// worn in a slot, its bonuses are added to the wearer's stats
+    Equipment(Equipment),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    MainHand,
+    OffHand,
+    Body,
+    Head,
+    Ring,
+}
+
+impl Slot {
+    /// how many items can be worn in this slot at once
+    pub fn capacity(self) -> usize {
+        match self {
+            Slot::Ring => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Slot::MainHand => "main hand",
+            Slot::OffHand => "off hand",
+            Slot::Body => "body",
+            Slot::Head => "head",
+            Slot::Ring => "ring",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    /// added to melee hits, or to shots for a ranged weapon
+    pub damage: i32,
+    pub defense: i32,
+    pub max_hp: i32,
+    /// how far its wearer can shoot, 0 for melee weapons and armour
+    pub range: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Item {
+    position: Coordinates,
+    char: char,
+    color: Color,
+    name: String,
+    kind: ItemKind,
+}
+
+impl Item {
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        renderer.put_char(self.position.x, self.position.y, self.char, self.color);
+    }
+
+    pub fn set_position(&mut self, position: Coordinates) {
+        self.position = position;
+    }
+
+    pub fn get_position(&self) -> &Coordinates {
+        &self.position
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        self.kind
+    }
+
+    pub fn equipment(&self) -> Option<Equipment> {
+        match self.kind {
+            ItemKind::Equipment(equipment) => Some(equipment),
+            _ => None,
+        }
+    }
+
+    /// name with the slot the item is worn in, for menus
+    pub fn label(&self) -> String {
+        match self.equipment() {
+            Some(equipment) => format!("{} [{}]", self.name, equipment.slot.name()),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl Item {
+    pub fn healing_potion(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '!',
+            color: color::VIOLET,
+            name: "Healing potion".into(),
+            kind: ItemKind::Potion { heal: 10 },
+        }
+    }
+
+    pub fn haste_potion(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '!',
+            color: color::LIGHT_SKY,
+            name: "Potion of haste".into(),
+            kind: ItemKind::Elixir(StatusEffect::new(EffectKind::Haste, 20, 10)),
+        }
+    }
+
+    pub fn regeneration_potion(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '!',
+            color: color::LIGHT_PINK,
+            name: "Potion of regeneration".into(),
+            kind: ItemKind::Elixir(StatusEffect::new(EffectKind::Regeneration, 10, 2)),
+        }
+    }
+
+    pub fn lightning_scroll(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '?',
+            color: color::LIGHT_YELLOW,
+            name: "Scroll of lightning bolt".into(),
+            kind: ItemKind::Scroll {
+                damage: 20,
+                range: 5,
+            },
+        }
+    }
+
+    pub fn fire_bolt_scroll(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '?',
+            color: color::LIGHT_RED,
+            name: "Scroll of fire bolt".into(),
+            kind: ItemKind::Spell {
+                damage: 12,
+                range: 10,
+            },
+        }
+    }
+
+    pub fn stun_scroll(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '?',
+            color: color::LIGHT_SKY,
+            name: "Scroll of stunning".into(),
+            kind: ItemKind::Curse {
+                effect: StatusEffect::new(EffectKind::Stun, 3, 0),
+                range: 8,
+            },
+        }
+    }
+
+    pub fn throwing_knife(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: ')',
+            color: color::LIGHT_GREY,
+            name: "Throwing knife".into(),
+            kind: ItemKind::Thrown {
+                damage: 6,
+                range: 8,
+            },
+        }
+    }
+
+    pub fn sword(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '/',
+            color: color::SKY,
+            name: "Sword".into(),
+            kind: ItemKind::Equipment(Equipment {
+                slot: Slot::MainHand,
+                damage: 3,
+                defense: 0,
+                max_hp: 0,
+                range: 0,
+            }),
+        }
+    }
+
+    pub fn short_bow(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '}',
+            color: color::DARK_ORANGE,
+            name: "Short bow".into(),
+            kind: ItemKind::Equipment(Equipment {
+                slot: Slot::MainHand,
+                damage: 3,
+                defense: 0,
+                max_hp: 0,
+                range: 8,
+            }),
+        }
+    }
+
+    pub fn leather_armor(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '[',
+            color: color::DARKER_ORANGE,
+            name: "Leather armor".into(),
+            kind: ItemKind::Equipment(Equipment {
+                slot: Slot::Body,
+                damage: 0,
+                defense: 1,
+                max_hp: 0,
+                range: 0,
+            }),
+        }
+    }
+
+    pub fn shield(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '[',
+            color: color::DARKER_ORANGE,
+            name: "Wooden shield".into(),
+            kind: ItemKind::Equipment(Equipment {
+                slot: Slot::OffHand,
+                damage: 0,
+                defense: 1,
+                max_hp: 0,
+                range: 0,
+            }),
+        }
+    }
+
+    pub fn helmet(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '^',
+            color: color::LIGHT_GREY,
+            name: "Iron helmet".into(),
+            kind: ItemKind::Equipment(Equipment {
+                slot: Slot::Head,
+                damage: 0,
+                defense: 1,
+                max_hp: 0,
+                range: 0,
+            }),
+        }
+    }
+
+    pub fn ring_of_vitality(x: i32, y: i32) -> Self {
+        Self {
+            position: Coordinates { x, y },
+            char: '=',
+            color: color::GOLD,
+            name: "Ring of vitality".into(),
+            kind: ItemKind::Equipment(Equipment {
+                slot: Slot::Ring,
+                damage: 0,
+                defense: 0,
+                max_hp: 5,
+                range: 0,
+            }),
+        }
+    }
+}
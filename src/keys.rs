@@ -0,0 +1,497 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use serde::Deserialize;
+use tcod::input::{Key, KeyCode};
+
+pub const KEYS_PATH: &str = "data/keys.json";
+
+/// what the player wants to do, independent of the key it is bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveWest,
+    MoveEast,
+    MoveNorthWest,
+    MoveNorthEast,
+    MoveSouthWest,
+    MoveSouthEast,
+    PickUp,
+    UseItem,
+    Throw,
+    Fire,
+    DropItem,
+    Unequip,
+    CloseDoors,
+    Explore,
+    Wait,
+    Rest,
+    Descend,
+    Ascend,
+    Look,
+    Character,
+    History,
+    ScrollLogUp,
+    ScrollLogDown,
+    Help,
+    Fullscreen,
+    Restart,
+    Quit,
+}
+
+impl Action {
+    /// every action, in the order of the help screen
+    pub const ALL: [Action; 29] = [
+        Action::MoveNorth,
+        Action::MoveSouth,
+        Action::MoveWest,
+        Action::MoveEast,
+        Action::MoveNorthWest,
+        Action::MoveNorthEast,
+        Action::MoveSouthWest,
+        Action::MoveSouthEast,
+        Action::PickUp,
+        Action::UseItem,
+        Action::Throw,
+        Action::Fire,
+        Action::DropItem,
+        Action::Unequip,
+        Action::CloseDoors,
+        Action::Explore,
+        Action::Wait,
+        Action::Rest,
+        Action::Descend,
+        Action::Ascend,
+        Action::Look,
+        Action::Character,
+        Action::History,
+        Action::ScrollLogUp,
+        Action::ScrollLogDown,
+        Action::Help,
+        Action::Fullscreen,
+        Action::Restart,
+        Action::Quit,
+    ];
+
+    /// step of the movement actions
+    pub fn direction(self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveNorth => Some((0, -1)),
+            Action::MoveSouth => Some((0, 1)),
+            Action::MoveWest => Some((-1, 0)),
+            Action::MoveEast => Some((1, 0)),
+            Action::MoveNorthWest => Some((-1, -1)),
+            Action::MoveNorthEast => Some((1, -1)),
+            Action::MoveSouthWest => Some((-1, 1)),
+            Action::MoveSouthEast => Some((1, 1)),
+            _ => None,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::MoveNorth => "move or attack north",
+            Action::MoveSouth => "move or attack south",
+            Action::MoveWest => "move or attack west",
+            Action::MoveEast => "move or attack east",
+            Action::MoveNorthWest => "move or attack north-west",
+            Action::MoveNorthEast => "move or attack north-east",
+            Action::MoveSouthWest => "move or attack south-west",
+            Action::MoveSouthEast => "move or attack south-east",
+            Action::PickUp => "pick up an item",
+            Action::UseItem => "use an item",
+            Action::Throw => "throw an item",
+            Action::Fire => "fire the ranged weapon",
+            Action::DropItem => "drop an item",
+            Action::Unequip => "take off equipment",
+            Action::CloseDoors => "close the doors around you",
+            Action::Explore => "explore the level",
+            Action::Wait => "wait a turn",
+            Action::Rest => "rest until healed or disturbed",
+            Action::Descend => "go down the stairs",
+            Action::Ascend => "go up the stairs",
+            Action::Look => "look around",
+            Action::Character => "show the character",
+            Action::History => "show the message history",
+            Action::ScrollLogUp => "scroll the log to older messages",
+            Action::ScrollLogDown => "scroll the log to newer messages",
+            Action::Help => "show this help",
+            Action::Fullscreen => "toggle fullscreen",
+            Action::Restart => "start a new game",
+            Action::Quit => "save and quit",
+        }
+    }
+}
+
+/// a physical key, a printable character or a special key, optionally with alt held.
+/// Shift is ignored, like the rest of the input handling does
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    printable: char,
+    alt: bool,
+}
+
+/// names of the special keys in the config file
+const KEY_NAMES: [(&str, KeyCode); 29] = [
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("enter", KeyCode::Enter),
+    ("escape", KeyCode::Escape),
+    ("tab", KeyCode::Tab),
+    ("space", KeyCode::Spacebar),
+    ("backspace", KeyCode::Backspace),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("kp1", KeyCode::NumPad1),
+    ("kp2", KeyCode::NumPad2),
+    ("kp3", KeyCode::NumPad3),
+    ("kp4", KeyCode::NumPad4),
+    ("kp5", KeyCode::NumPad5),
+    ("kp6", KeyCode::NumPad6),
+    ("kp7", KeyCode::NumPad7),
+    ("kp8", KeyCode::NumPad8),
+    ("kp9", KeyCode::NumPad9),
+    ("kpenter", KeyCode::NumPadEnter),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+];
+
+impl KeyBinding {
+    /// parse a key written as a single character, like `g` or `>`, or a key name like `up`,
+    /// `kp8` or `pageup`, with an optional `alt+` in front
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (alt, name) = match text.strip_prefix("alt+") {
+            Some(name) => (true, name),
+            None => (false, text),
+        };
+
+        let mut chars = name.chars();
+
+        if let (Some(printable), None) = (chars.next(), chars.next()) {
+            return Ok(Self {
+                code: KeyCode::Char,
+                printable,
+                alt,
+            });
+        }
+
+        let lowercase = name.to_lowercase();
+
+        KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| *key_name == lowercase)
+            .map(|&(_, code)| Self {
+                code,
+                printable: '\0',
+                alt,
+            })
+            .ok_or_else(|| format!("unknown key {:?}", text))
+    }
+
+    fn matches(&self, key: &Key) -> bool {
+        key.code == self.code
+            && (self.code != KeyCode::Char || key.printable == self.printable)
+            && key.alt == self.alt
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+
+        if self.code == KeyCode::Char {
+            return write!(f, "{}", self.printable);
+        }
+
+        match KEY_NAMES.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => write!(f, "{:?}", self.code),
+        }
+    }
+}
+
+/// ready-made sets of bindings, they differ in the movement keys and whatever those displace
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// `wasd` with `qezc` for the diagonals
+    #[default]
+    Wasd,
+    Numpad,
+    /// `hjkl` with `yubn` for the diagonals
+    Vi,
+}
+
+impl Preset {
+    /// bindings of the preset as written in the config file
+    fn bindings(self) -> Vec<(Action, &'static [&'static str])> {
+        let mut bindings: Vec<(Action, &'static [&'static str])> = vec![
+            (Action::PickUp, &["g"]),
+            (Action::UseItem, &["i"]),
+            (Action::Throw, &["t"]),
+            (Action::Fire, &["f"]),
+            (Action::DropItem, &["x"]),
+            (Action::Explore, &["o"]),
+            (Action::Wait, &["."]),
+            (Action::Rest, &["p"]),
+            (Action::Descend, &[">"]),
+            (Action::Ascend, &["<"]),
+            (Action::History, &["m"]),
+            (Action::ScrollLogUp, &["pageup"]),
+            (Action::ScrollLogDown, &["pagedown"]),
+            (Action::Help, &["?", "f1"]),
+            (Action::Fullscreen, &["alt+enter"]),
+            (Action::Restart, &["r"]),
+            (Action::Quit, &["escape"]),
+        ];
+
+        bindings.extend(match self {
+            Preset::Wasd => vec![
+                (Action::MoveNorth, &["w", "up"] as &[&str]),
+                (Action::MoveSouth, &["s", "down"]),
+                (Action::MoveWest, &["a", "left"]),
+                (Action::MoveEast, &["d", "right"]),
+                (Action::MoveNorthWest, &["q"]),
+                (Action::MoveNorthEast, &["e"]),
+                (Action::MoveSouthWest, &["z"]),
+                (Action::MoveSouthEast, &["c"]),
+                (Action::Unequip, &["u"]),
+                (Action::CloseDoors, &["k"]),
+                (Action::Look, &["l"]),
+                (Action::Character, &["v"]),
+            ],
+            Preset::Numpad => vec![
+                (Action::MoveNorth, &["kp8", "up"] as &[&str]),
+                (Action::MoveSouth, &["kp2", "down"]),
+                (Action::MoveWest, &["kp4", "left"]),
+                (Action::MoveEast, &["kp6", "right"]),
+                (Action::MoveNorthWest, &["kp7"]),
+                (Action::MoveNorthEast, &["kp9"]),
+                (Action::MoveSouthWest, &["kp1"]),
+                (Action::MoveSouthEast, &["kp3"]),
+                (Action::Wait, &["kp5"]),
+                (Action::Unequip, &["e"]),
+                (Action::CloseDoors, &["k"]),
+                (Action::Look, &["l"]),
+                (Action::Character, &["c"]),
+            ],
+            Preset::Vi => vec![
+                (Action::MoveNorth, &["k", "up"] as &[&str]),
+                (Action::MoveSouth, &["j", "down"]),
+                (Action::MoveWest, &["h", "left"]),
+                (Action::MoveEast, &["l", "right"]),
+                (Action::MoveNorthWest, &["y"]),
+                (Action::MoveNorthEast, &["u"]),
+                (Action::MoveSouthWest, &["b"]),
+                (Action::MoveSouthEast, &["n"]),
+                (Action::Unequip, &["e"]),
+                (Action::CloseDoors, &["v"]),
+                (Action::Look, &[";"]),
+                (Action::Character, &["c"]),
+            ],
+        });
+
+        bindings
+    }
+}
+
+/// the key bindings file: a preset and the actions whose keys are changed from it
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeysConfig {
+    #[serde(default)]
+    preset: Preset,
+    /// replaces all keys of each listed action
+    #[serde(default)]
+    bindings: HashMap<Action, Vec<String>>,
+}
+
+/// which action each key triggers
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl KeyBindings {
+    pub fn preset(preset: Preset) -> Self {
+        Self::new(preset, &HashMap::new()).expect("presets use valid keys")
+    }
+
+    fn new(preset: Preset, overrides: &HashMap<Action, Vec<String>>) -> Result<Self, String> {
+        let mut bindings = vec![];
+
+        for (action, keys) in preset.bindings() {
+            if overrides.contains_key(&action) {
+                continue;
+            }
+
+            for key in keys {
+                bindings.push((KeyBinding::parse(key)?, action));
+            }
+        }
+
+        // actions missing from the preset can only come from the file, keep them in order
+        for action in Action::ALL {
+            for key in overrides.get(&action).into_iter().flatten() {
+                let binding = KeyBinding::parse(key)?;
+
+                if let Some((_, other)) = bindings.iter().find(|(other, _)| *other == binding) {
+                    return Err(format!(
+                        "key {:?} of {:?} is already bound to {:?}",
+                        key, action, other
+                    ));
+                }
+
+                bindings.push((binding, action));
+            }
+        }
+
+        Ok(Self { bindings })
+    }
+
+    /// read the bindings from a JSON file, the default preset is used when there is no file
+    pub fn load(path: &str) -> Result<Self, String> {
+        if !Path::new(path).exists() {
+            return Ok(Self::preset(Preset::default()));
+        }
+
+        let data = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        let config: KeysConfig =
+            serde_json::from_str(&data).map_err(|err| format!("{}: {}", path, err))?;
+
+        Self::new(config.preset, &config.bindings).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    /// action bound to the pressed key
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(key))
+            .map(|&(_, action)| action)
+    }
+
+    /// keys bound to the action, in the order they were bound
+    pub fn keys(&self, action: Action) -> Vec<KeyBinding> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|&(binding, _)| binding)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESETS: [Preset; 3] = [Preset::Wasd, Preset::Numpad, Preset::Vi];
+
+    fn binding(code: KeyCode, printable: char, alt: bool) -> KeyBinding {
+        KeyBinding {
+            code,
+            printable,
+            alt,
+        }
+    }
+
+    fn key(code: KeyCode, printable: char) -> Key {
+        let mut key = Key::default();
+        key.code = code;
+        key.printable = printable;
+        key
+    }
+
+    /// the bindings a config file with `json` in it gives
+    fn from_config(json: &str) -> Result<KeyBindings, String> {
+        let config: KeysConfig = serde_json::from_str(json).map_err(|err| err.to_string())?;
+
+        KeyBindings::new(config.preset, &config.bindings)
+    }
+
+    #[test]
+    fn keys_are_parsed_by_character_or_name() {
+        for (text, expected) in [
+            ("g", binding(KeyCode::Char, 'g', false)),
+            (">", binding(KeyCode::Char, '>', false)),
+            ("up", binding(KeyCode::Up, '\0', false)),
+            ("PageUp", binding(KeyCode::PageUp, '\0', false)),
+            ("kp8", binding(KeyCode::NumPad8, '\0', false)),
+            ("alt+enter", binding(KeyCode::Enter, '\0', true)),
+            ("alt+x", binding(KeyCode::Char, 'x', true)),
+        ] {
+            let parsed = KeyBinding::parse(text).unwrap();
+
+            assert_eq!(parsed, expected, "{}", text);
+            assert_eq!(KeyBinding::parse(&parsed.to_string()), Ok(parsed));
+        }
+    }
+
+    #[test]
+    fn empty_or_unknown_keys_are_refused() {
+        for text in ["", "alt+", "ctrl+x", "upp", "kp0"] {
+            assert!(KeyBinding::parse(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn overrides_replace_the_keys_of_an_action() {
+        let keys = from_config(r#"{ "preset": "vi", "bindings": { "history": ["alt+m", "f2"] } }"#)
+            .unwrap();
+
+        assert_eq!(keys.action(&key(KeyCode::Char, 'm')), None);
+        assert_eq!(keys.action(&key(KeyCode::F2, '\0')), Some(Action::History));
+        assert_eq!(
+            keys.action(&key(KeyCode::Char, 'h')),
+            Some(Action::MoveWest)
+        );
+    }
+
+    #[test]
+    fn overrides_cannot_take_a_key_that_is_already_bound() {
+        for json in [
+            r#"{ "bindings": { "pick_up": ["w"] } }"#,
+            r#"{ "bindings": { "pick_up": ["y"], "throw": ["y"] } }"#,
+            r#"{ "preset": "numpad", "bindings": { "look": ["kp5"] } }"#,
+            r#"{ "bindings": { "look": ["alt+enter"] } }"#,
+        ] {
+            assert!(from_config(json).is_err(), "{}", json);
+        }
+
+        assert!(from_config(r#"{ "bindings": { "pick_up": ["unknown"] } }"#).is_err());
+    }
+
+    #[test]
+    fn every_preset_binds_every_action_to_keys_of_its_own() {
+        for preset in PRESETS {
+            let keys = KeyBindings::preset(preset);
+
+            for action in Action::ALL {
+                assert!(!keys.keys(action).is_empty(), "{:?} {:?}", preset, action);
+            }
+
+            for (index, (binding, _)) in keys.bindings.iter().enumerate() {
+                assert!(
+                    !keys.bindings[..index]
+                        .iter()
+                        .any(|(other, _)| other == binding),
+                    "{:?} binds {} twice",
+                    preset,
+                    binding
+                );
+            }
+        }
+    }
+}
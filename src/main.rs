@@ -1,34 +1,58 @@
+mod camera;
+mod color;
+mod effect;
+mod fov;
+mod game;
+mod generator;
+mod item;
+mod keys;
 mod map;
+mod monsters;
+mod path;
+mod render;
+mod save;
+mod tile;
 mod unit;
 mod gui;
 
-use std::process::Command;
+use camera::Camera;
 use gui::GUI;
+use render::Renderer;
 use tcod::{
     colors,
     console::{blit, Offscreen, Root},
-    input::{Key, KeyCode},
+    input::{self, Key, KeyCode},
     BackgroundFlag, Console, FontLayout, FontType, TextAlignment,
 };
 
-use map::Map;
-use unit::UserActions;
+use game::{Command, Event, Game, Message};
+use generator::GeneratorKind;
+use item::ItemKind;
+use keys::{Action, KeyBindings, KEYS_PATH};
+use monsters::{MonsterTemplates, MONSTERS_PATH};
+use unit::{Coordinates, UserActions};
 
 const WINDOW_WIDTH: i32 = 100;
 const WINDOW_HEIGHT: i32 = 100;
 
 const GUI_HEIGHT: i32 = 30;
-const MAP_HEIGHT: i32 = WINDOW_HEIGHT - GUI_HEIGHT;
+const VIEW_HEIGHT: i32 = WINDOW_HEIGHT - GUI_HEIGHT;
 
-const FPS: i32 = 60;
+const MAP_WIDTH: i32 = 200;
+const MAP_HEIGHT: i32 = 140;
 
-pub struct Game {
-    pub map: Map,
-}
+/// the camera scrolls once the player walks out of this area in the middle of the view
+const CAMERA_DEAD_ZONE: (i32, i32) = (30, 20);
+
+const FPS: i32 = 60;
 
 struct App {
     root: Root,
     offscreen: Offscreen,
+    camera: Camera,
+    keys: KeyBindings,
+    /// console cell the mouse was last seen over
+    mouse: Option<(i32, i32)>,
     gui: GUI,
     game: Game,
 }
@@ -37,7 +61,7 @@ fn restart() {
     let path_to_app = std::env::current_exe();
 
     if let Ok(path_to_app) = path_to_app {
-        Command::new(path_to_app)
+        std::process::Command::new(path_to_app)
             .spawn()
             .expect("failed to restart process");
         std::process::exit(0);
@@ -46,114 +70,512 @@ fn restart() {
     }
 }
 
-fn handle_keys(app: &mut App) -> UserActions {
-    use UserActions::*;
+/// seed passed as `--seed <number>`, if any
+fn parse_seed() -> Option<u64> {
+    let args: Vec<String> = std::env::args().collect();
 
-    let key = app.root.wait_for_keypress(true);
+    args.iter()
+        .position(|arg| arg == "--seed")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|seed| seed.parse().ok())
+}
 
-    match (key, app.game.map.get_player().is_alive()) {
-        (
-            Key {
-                code: KeyCode::Char,
-                printable: 'w',
-                ..
-            },
-            true,
-        ) => {
-            if app.game.map.player_move_or_attack(0, -1) {
-                TookTurn
-            } else {
-                DidNotTakeTurn
+/// generator chosen with `--generator <rooms|bsp|caves>`, random rooms by default
+fn parse_generator() -> GeneratorKind {
+    let args: Vec<String> = std::env::args().collect();
+
+    args.iter()
+        .position(|arg| arg == "--generator")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|name| GeneratorKind::from_name(name))
+        .unwrap_or(GeneratorKind::RandomRooms)
+}
+
+/// an explicit seed always starts a new game, otherwise the saved game is resumed if there is one
+fn start_game(gui: &mut GUI, templates: MonsterTemplates) -> Game {
+    let seed = parse_seed();
+
+    if seed.is_none() && save::save_exists() {
+        match save::load_game(templates.clone()) {
+            Ok(game) => {
+                gui.add_message(Message::new("Welcome back, stranger!", color::LIGHT_YELLOW));
+
+                return game;
             }
+            Err(err) => gui.add_message(Message::new(
+                format!("Could not load the saved game: {}", err),
+                color::LIGHT_RED,
+            )),
         }
+    }
 
-        (
-            Key {
-                code: KeyCode::Char,
-                printable: 's',
-                ..
-            },
-            true,
-        ) => {
-            if app.game.map.player_move_or_attack(0, 1) {
-                TookTurn
-            } else {
-                DidNotTakeTurn
+    gui.add_message(Message::new(
+        "Welcome, stranger! Prepare to perish in the dungeon.",
+        color::LIGHT_YELLOW,
+    ));
+
+    Game::new(
+        MAP_WIDTH,
+        MAP_HEIGHT,
+        seed.unwrap_or_else(rand::random),
+        parse_generator(),
+        templates,
+    )
+}
+
+/// hand the command over to the game and its outcome to the GUI and the save file
+fn apply_command(app: &mut App, command: Command) -> UserActions {
+    let events = app.game.apply(command);
+
+    handle_events(app, events)
+}
+
+/// present what happened while a command was carried out
+fn handle_events(app: &mut App, events: Vec<Event>) -> UserActions {
+    let mut user_action = UserActions::DidNotTakeTurn;
+
+    for event in events {
+        match event {
+            Event::Message(message) => app.gui.add_message(message),
+            Event::TurnTaken => user_action = UserActions::TookTurn,
+            Event::LevelUp => {
+                let stat = app
+                    .gui
+                    .level_up_menu(&mut app.root, app.game.map().player());
+
+                apply_command(app, Command::LevelUp(stat));
             }
+            Event::PlayerDied => save::delete_save(),
+            Event::Disturbed => {}
         }
+    }
 
-        (
-            Key {
-                code: KeyCode::Char,
-                printable: 'a',
-                ..
-            },
-            true,
-        ) => {
-            if app.game.map.player_move_or_attack(-1, 0) {
-                TookTurn
-            } else {
-                DidNotTakeTurn
+    user_action
+}
+
+/// draw the part of the level around the player into the offscreen console
+fn render_map(app: &mut App) {
+    app.offscreen.set_default_background(colors::BLACK);
+    app.offscreen.clear();
+
+    let map = app.game.map();
+    app.camera.follow(
+        map.player().get_position(),
+        (map.get_width(), map.get_height()),
+    );
+    map.render(&mut app.camera.view(&mut app.offscreen));
+}
+
+/// put the rendered map and the GUI on the screen
+fn present(app: &mut App) {
+    blit(
+        &app.offscreen,
+        (0, 0),
+        (WINDOW_WIDTH, VIEW_HEIGHT),
+        &mut app.root,
+        (0, 0),
+        1.0,
+        1.0,
+    );
+
+    app.gui.render(&mut app.root, &app.game, VIEW_HEIGHT);
+}
+
+/// direction the movement keys move a cursor in
+fn cursor_step(app: &App, key: &Key) -> Option<(i32, i32)> {
+    app.keys.action(key).and_then(Action::direction)
+}
+
+/// move the cursor by the step, keeping it on the map
+fn move_cursor(app: &App, cursor: &mut Coordinates, (dx, dy): (i32, i32)) {
+    let map = app.game.map();
+
+    cursor.x = (cursor.x + dx).clamp(0, map.get_width() - 1);
+    cursor.y = (cursor.y + dy).clamp(0, map.get_height() - 1);
+}
+
+/// world tile under the console cell, if the cell shows the map
+fn map_tile(app: &App, (x, y): (i32, i32)) -> Option<Coordinates> {
+    if y < VIEW_HEIGHT {
+        Some(app.camera.to_world(x, y))
+    } else {
+        None
+    }
+}
+
+/// show the rendered frame with a line of help on top
+fn present_with_prompt(app: &mut App, prompt: &str) {
+    present(app);
+
+    app.root.set_default_foreground(colors::WHITE);
+    app.root
+        .print_ex(1, 0, BackgroundFlag::None, TextAlignment::Left, prompt);
+    app.root.flush();
+}
+
+/// let the player pick a target tile within `range`, starting on the closest visible monster.
+/// The cursor is moved with the movement keys or the mouse and tab jumps to the next monster
+/// in sight, enter, the fire key or a click confirm and escape cancels
+fn choose_target(app: &mut App, range: i32) -> Option<Coordinates> {
+    let player = *app.game.map().player().get_position();
+    let monsters = app.game.map().visible_monsters();
+    let mut next_monster = 0;
+    let mut cursor = player;
+
+    if let Some(monster) = monsters.first() {
+        cursor = *monster;
+        next_monster = 1;
+    }
+
+    while !app.root.window_closed() {
+        render_map(app);
+
+        let line = app.game.map().line_of_fire(&player, &cursor, range);
+        let in_reach = line.last() == Some(&cursor);
+
+        {
+            let mut view = app.camera.view(&mut app.offscreen);
+
+            for tile in &line {
+                view.set_background(tile.x, tile.y, color::DARK_YELLOW);
             }
+
+            let cursor_color = if in_reach { color::YELLOW } else { color::RED };
+            view.set_background(cursor.x, cursor.y, cursor_color);
         }
 
-        (
-            Key {
-                code: KeyCode::Char,
-                printable: 'd',
-                ..
-            },
-            true,
-        ) => {
-            if app.game.map.player_move_or_attack(1, 0) {
-                TookTurn
-            } else {
-                DidNotTakeTurn
+        present_with_prompt(
+            app,
+            "Choose a target: move or point, tab for the next monster, enter to confirm, escape to cancel",
+        );
+
+        match input::check_for_event(input::KEY_PRESS | input::MOUSE) {
+            Some((_, input::Event::Mouse(mouse))) => {
+                if let Some(tile) = map_tile(app, (mouse.cx as i32, mouse.cy as i32)) {
+                    cursor = tile;
+
+                    if mouse.lbutton_pressed {
+                        return Some(cursor);
+                    }
+                }
             }
+
+            Some((_, input::Event::Key(key))) => match (key.code, app.keys.action(&key)) {
+                (KeyCode::Escape, _) => return None,
+                (KeyCode::Enter, _) | (_, Some(Action::Fire)) => return Some(cursor),
+                (KeyCode::Tab, _) if !monsters.is_empty() => {
+                    cursor = monsters[next_monster % monsters.len()];
+                    next_monster += 1;
+                }
+                _ => {
+                    if let Some(step) = cursor_step(app, &key) {
+                        move_cursor(app, &mut cursor, step);
+                    }
+                }
+            },
+
+            _ => {}
         }
+    }
+
+    None
+}
 
-        (
-            Key {
-                code: KeyCode::Char,
-                printable: 'r',
-                ..
+/// move a cursor over the map with the keys or the mouse and describe whatever is under it in
+/// the GUI panel, until escape or the look key is pressed
+fn look_mode(app: &mut App) {
+    let mut cursor = *app.game.map().player().get_position();
+
+    while !app.root.window_closed() {
+        render_map(app);
+
+        app.camera
+            .view(&mut app.offscreen)
+            .set_background(cursor.x, cursor.y, color::LIGHT_GREY);
+
+        let description = app
+            .game
+            .map()
+            .describe(cursor.x, cursor.y)
+            .unwrap_or_else(|| {
+                vec![Message::new(
+                    "You have not seen this place yet",
+                    color::LIGHT_GREY,
+                )]
+            });
+        app.gui.set_description(Some(description));
+
+        present_with_prompt(
+            app,
+            "Look around: move or point at a tile, escape to stop looking",
+        );
+
+        match input::check_for_event(input::KEY_PRESS | input::MOUSE) {
+            Some((_, input::Event::Mouse(mouse))) => {
+                if let Some(tile) = map_tile(app, (mouse.cx as i32, mouse.cy as i32)) {
+                    cursor = tile;
+                }
+            }
+
+            Some((_, input::Event::Key(key))) => match (key.code, app.keys.action(&key)) {
+                (KeyCode::Escape, _) | (_, Some(Action::Look)) => break,
+                _ => {
+                    if let Some(step) = cursor_step(app, &key) {
+                        move_cursor(app, &mut cursor, step);
+                    }
+                }
             },
-            _,
-        ) => {
+
+            _ => {}
+        }
+    }
+
+    app.gui.set_description(None);
+}
+
+/// apply the one-step command every frame until it stops taking turns, the game reports the
+/// player is disturbed or a key is pressed
+fn repeat_command(app: &mut App, command: Command) -> UserActions {
+    let mut user_action = UserActions::DidNotTakeTurn;
+
+    loop {
+        let events = app.game.apply(command);
+        let disturbed = events.iter().any(|event| matches!(event, Event::Disturbed));
+
+        if handle_events(app, events) != UserActions::TookTurn {
+            break;
+        }
+
+        user_action = UserActions::TookTurn;
+
+        if disturbed {
+            break;
+        }
+
+        render_map(app);
+        present(app);
+        app.root.flush();
+
+        if let Some((_, input::Event::Key(_))) = input::check_for_event(input::KEY_PRESS) {
+            break;
+        }
+    }
+
+    user_action
+}
+
+/// keep track of the mouse for the tooltip and travel to the tile under a left click
+fn handle_mouse(app: &mut App, mouse: &input::Mouse) -> UserActions {
+    let cell = (mouse.cx as i32, mouse.cy as i32);
+    app.mouse = Some(cell);
+
+    match map_tile(app, cell) {
+        Some(target) if mouse.lbutton_pressed && app.game.map().player().is_alive() => {
+            repeat_command(app, Command::Travel { target })
+        }
+        _ => UserActions::DidNotTakeTurn,
+    }
+}
+
+fn handle_keys(app: &mut App, key: Key) -> UserActions {
+    use UserActions::*;
+
+    let Some(action) = app.keys.action(&key) else {
+        return DidNotTakeTurn;
+    };
+    let alive = app.game.map().player().is_alive();
+
+    if let Some((dx, dy)) = action.direction() {
+        return if alive {
+            apply_command(app, Command::Move { dx, dy })
+        } else {
+            DidNotTakeTurn
+        };
+    }
+
+    match (action, alive) {
+        (Action::PickUp, true) => apply_command(app, Command::PickUp),
+
+        (Action::UseItem, true) => {
+            let choice = app.gui.inventory_menu(
+                &mut app.root,
+                "Press the key next to an item to use it, or any other to cancel.\n",
+                app.game.map().player().inventory(),
+            );
+
+            let kind = choice.map(|index| app.game.map().player().inventory()[index].kind());
+
+            match (choice, kind) {
+                (
+                    Some(index),
+                    Some(ItemKind::Spell { range, .. } | ItemKind::Curse { range, .. }),
+                ) => match choose_target(app, range) {
+                    Some(target) => apply_command(app, Command::UseItemOn { index, target }),
+                    None => DidNotTakeTurn,
+                },
+                (Some(index), _) => apply_command(app, Command::UseItem(index)),
+                (None, _) => DidNotTakeTurn,
+            }
+        }
+
+        (Action::Throw, true) => {
+            let choice = app.gui.inventory_menu(
+                &mut app.root,
+                "Press the key next to an item to throw it, or any other to cancel.\n",
+                app.game.map().player().inventory(),
+            );
+
+            let Some(index) = choice else {
+                return DidNotTakeTurn;
+            };
+
+            let item = &app.game.map().player().inventory()[index];
+
+            match item.kind() {
+                ItemKind::Thrown { range, .. } => match choose_target(app, range) {
+                    Some(target) => apply_command(app, Command::Throw { index, target }),
+                    None => DidNotTakeTurn,
+                },
+                _ => {
+                    app.gui.add_message(Message::new(
+                        format!("{} is not made for throwing", item.name()),
+                        color::LIGHT_GREY,
+                    ));
+
+                    DidNotTakeTurn
+                }
+            }
+        }
+
+        (Action::Fire, true) => {
+            let range = app.game.map().player().range();
+
+            if range == 0 {
+                app.gui.add_message(Message::new(
+                    "You have no ranged weapon equipped",
+                    color::LIGHT_GREY,
+                ));
+
+                return DidNotTakeTurn;
+            }
+
+            match choose_target(app, range) {
+                Some(target) => apply_command(app, Command::Fire { target }),
+                None => DidNotTakeTurn,
+            }
+        }
+
+        (Action::DropItem, true) => {
+            let choice = app.gui.inventory_menu(
+                &mut app.root,
+                "Press the key next to an item to drop it, or any other to cancel.\n",
+                app.game.map().player().inventory(),
+            );
+
+            match choice {
+                Some(index) => apply_command(app, Command::DropItem(index)),
+                None => DidNotTakeTurn,
+            }
+        }
+
+        (Action::Unequip, true) => {
+            let choice = app.gui.inventory_menu(
+                &mut app.root,
+                "Press the key next to an item to take it off, or any other to cancel.\n",
+                app.game.map().player().equipment(),
+            );
+
+            match choice {
+                Some(index) => apply_command(app, Command::Unequip(index)),
+                None => DidNotTakeTurn,
+            }
+        }
+
+        (Action::CloseDoors, true) => apply_command(app, Command::CloseDoors),
+
+        (Action::Explore, true) => repeat_command(app, Command::Explore),
+
+        (Action::Wait, true) => apply_command(app, Command::Wait),
+
+        (Action::Rest, true) => repeat_command(app, Command::Rest),
+
+        (Action::Look, _) => {
+            look_mode(app);
+
+            DidNotTakeTurn
+        }
+
+        (Action::Help, _) => {
+            app.gui.show_help(&mut app.root, &app.keys);
+
+            DidNotTakeTurn
+        }
+
+        (Action::Character, _) => {
+            app.gui.show_character(&mut app.root, app.game.map().player());
+
+            DidNotTakeTurn
+        }
+
+        (Action::Descend, true) => apply_command(app, Command::Descend),
+
+        (Action::Ascend, true) => apply_command(app, Command::Ascend),
+
+        (Action::Restart, _) => {
+            save::delete_save();
             restart();
 
             Exit
         }
 
-        (
-            Key {
-                code: KeyCode::Enter,
-                alt: true,
-                ..
-            },
-            _,
-        ) => {
+        (Action::ScrollLogUp, _) => {
+            app.gui.scroll_log(1);
+
+            DidNotTakeTurn
+        }
+
+        (Action::ScrollLogDown, _) => {
+            app.gui.scroll_log(-1);
+
+            DidNotTakeTurn
+        }
+
+        (Action::History, _) => {
+            app.gui.show_history(&mut app.root, &app.keys);
+
+            DidNotTakeTurn
+        }
+
+        (Action::Fullscreen, _) => {
             let fullscreen = app.root.is_fullscreen();
             app.root.set_fullscreen(!fullscreen);
 
             DidNotTakeTurn
         }
 
-        (
-            Key {
-                code: KeyCode::Escape,
-                ..
-            },
-            _,
-        ) => {
-            std::process::exit(0);
-        }
+        (Action::Quit, _) => Exit,
 
         _ => DidNotTakeTurn,
     }
 }
 
 fn main() {
+    let templates = match MonsterTemplates::load(MONSTERS_PATH) {
+        Ok(templates) => templates,
+        Err(err) => {
+            eprintln!("failed to load monsters: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let keys = match KeyBindings::load(KEYS_PATH) {
+        Ok(keys) => keys,
+        Err(err) => {
+            eprintln!("failed to load key bindings: {}", err);
+            std::process::exit(1);
+        }
+    };
+
     tcod::system::set_fps(FPS);
 
     let root = Root::initializer()
@@ -163,55 +585,44 @@ fn main() {
         .title("Roguelike game")
         .init();
 
+    let mut gui = GUI::new(WINDOW_WIDTH, GUI_HEIGHT);
+    let game = start_game(&mut gui, templates);
+
     let mut app = App {
         root,
-        offscreen: Offscreen::new(WINDOW_WIDTH, MAP_HEIGHT),
-        game: Game {
-            map: Map::new(WINDOW_WIDTH, MAP_HEIGHT),
-        },
-        gui: GUI::new(WINDOW_WIDTH, GUI_HEIGHT)
+        offscreen: Offscreen::new(WINDOW_WIDTH, VIEW_HEIGHT),
+        camera: Camera::new(WINDOW_WIDTH, VIEW_HEIGHT, CAMERA_DEAD_ZONE),
+        keys,
+        mouse: None,
+        game,
+        gui,
     };
 
-    app.game.map.set_fov();
-
     loop {
-        app.offscreen.set_default_background(colors::BLACK);
-        app.offscreen.clear();
-
-        app.game.map.render(&mut app.offscreen);
-
-        app.root.set_default_foreground(colors::WHITE);
-        app.root.set_default_background(colors::GREEN);
-        app.root.print_ex(
-            1,
-            GUI_HEIGHT - 2,
-            BackgroundFlag::None,
-            TextAlignment::Left,
-            format!(
-                "HP: {}/{}",
-                app.game.map.get_player().current_hp(),
-                app.game.map.get_player().max_hp()
-            ),
-        );
+        render_map(&mut app);
 
-        blit(
-            &app.offscreen,
-            (0, 0),
-            (WINDOW_WIDTH, MAP_HEIGHT),
-            &mut app.root,
-            (0, 0),
-            1.0,
-            1.0,
-        );
+        let hovered = app.mouse.and_then(|cell| map_tile(&app, cell));
+        let names = hovered.and_then(|tile| app.game.map().names_at(tile.x, tile.y));
+        app.gui.set_hover(names);
+
+        present(&mut app);
 
         app.root.flush();
 
-        let user_action = handle_keys(&mut app);
+        let user_action = match input::check_for_event(input::KEY_PRESS | input::MOUSE) {
+            Some((_, input::Event::Key(key))) => handle_keys(&mut app, key),
+            Some((_, input::Event::Mouse(mouse))) => handle_mouse(&mut app, &mouse),
+            None => UserActions::DidNotTakeTurn,
+        };
 
         if app.root.window_closed() || user_action == UserActions::Exit {
             break;
         }
+    }
 
-        app.game.map.monsters_action(user_action);
+    if app.game.map().player().is_alive() {
+        if let Err(err) = save::save_game(&app.game) {
+            eprintln!("failed to save the game: {}", err);
+        }
     }
 }
@@ -1,83 +1,37 @@
-use std::cmp::min;
-
-use rand::Rng;
-use tcod::{
-    console::Offscreen,
-    map::{FovAlgorithm, Map as FovMap},
-    BackgroundFlag, Color, Console,
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::color;
+use crate::effect::{EffectKind, StatusEffect};
+use crate::fov::compute_fov;
+use crate::game::Message;
+use crate::generator::{check_connectivity, repair_connectivity, ConnectivityReport, Generator};
+use crate::item::{Item, ItemKind};
+use crate::monsters::MonsterTemplates;
+use crate::path::{dijkstra_map, downhill_step, find_path, line};
+use crate::render::Renderer;
+use crate::tile::{Door, Tile, TileKind};
+use crate::unit::{
+    Coordinates, MonsterState, Stat, Unit, UnitActions, ATTACK_COST, ITEM_COST, MOVE_COST,
+    REST_COST, WAIT_COST,
 };
 
-use crate::unit::{Coordinates, Unit, UnitActions, UserActions};
-
-const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic; // default FOV algorithm
-const FOV_LIGHT_WALLS: bool = true; // light walls or not
 const TORCH_RADIUS: i32 = 10;
 
-const ROOM_MAX_SIZE: i32 = 10;
-const ROOM_MIN_SIZE: i32 = 6;
-const MAX_ROOMS: i32 = 30;
-
-const MAX_ROOM_MONSTERS: i32 = 3;
-
-const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
-const COLOR_LIGHT_WALL: Color = Color {
-    r: 130,
-    g: 110,
-    b: 50,
-};
-const COLOR_DARK_GROUND: Color = Color {
-    r: 50,
-    g: 50,
-    b: 150,
-};
-const COLOR_LIGHT_GROUND: Color = Color {
-    r: 200,
-    g: 180,
-    b: 50,
-};
-
-#[derive(Debug, Clone, Copy)]
-pub struct Tile {
-    blocked: bool,
-    explored: bool,
-    block_sight: bool,
-}
-
-impl Tile {
-    pub fn empty() -> Self {
-        Tile {
-            blocked: false,
-            explored: false,
-            block_sight: false,
-        }
-    }
-
-    pub fn wall() -> Self {
-        Tile {
-            blocked: true,
-            explored: false,
-            block_sight: true,
-        }
-    }
-
-    pub fn set_explored(&mut self, value: bool) {
-        self.explored = value;
-    }
+/// times a generator may try to build a level before the level falls back to a single room
+const GENERATION_ATTEMPTS: u32 = 5;
 
-    pub fn is_blocked(self) -> bool {
-        self.blocked
-    }
+/// how many tiles a monster's path search may expand before it falls back to a direct step
+const MONSTER_PATH_LIMIT: usize = 400;
 
-    pub fn is_block_sight(self) -> bool {
-        self.block_sight
-    }
+/// chance per turn that a sleeping monster with the player in sight wakes up
+const WAKE_UP_CHANCE: f64 = 0.3;
 
-    pub fn is_explored(self) -> bool {
-        self.explored
-    }
-}
+const MAX_ROOM_MONSTERS: i32 = 3;
+const MAX_ROOM_ITEMS: i32 = 2;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Room {
     pub left_x: i32,
     pub top_y: i32,
@@ -119,6 +73,10 @@ impl Room {
             && (self.bottom_y < room.top_y)
     }
 
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.left_x && x < self.right_x && y >= self.bottom_y && y < self.top_y
+    }
+
     pub fn intersects_with_as_rooms(&self, room: &Room) -> bool {
         (self.left_x <= room.right_x)
             && (self.right_x >= room.left_x)
@@ -127,112 +85,87 @@ impl Room {
     }
 }
 
-fn h_v_tunnel(new_center: &Coordinates, prev_center: &Coordinates, map: &mut Map) {
-    let (bottom_y, top_y) = (prev_center.y, prev_center.y + 1);
-    let (left_x, right_x) = if new_center.x < prev_center.x {
-        (new_center.x, prev_center.x)
-    } else {
-        (prev_center.x, new_center.x)
-    };
-
-    let h_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
-    h_tunnel.fill(map);
-
-    let (left_x, right_x) = (new_center.x, new_center.x + 1);
-    let (bottom_y, top_y) = if new_center.y < prev_center.y {
-        (new_center.y, prev_center.y)
-    } else {
-        (prev_center.y, new_center.y)
-    };
-
-    let v_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
-    v_tunnel.fill(map);
-
-    if !h_tunnel.intersects_with_as_tunnels(&v_tunnel) {
-        let v_tunnel = Room::new(left_x, right_x, bottom_y, top_y + 1);
-        v_tunnel.fill(map);
-    }
-}
-
-fn v_h_tunnel(new_center: &Coordinates, prev_center: &Coordinates, map: &mut Map) {
-    let (left_x, right_x) = (prev_center.x, prev_center.x + 1);
-    let (bottom_y, top_y) = if new_center.y < prev_center.y {
-        (new_center.y, prev_center.y)
-    } else {
-        (prev_center.y, new_center.y)
-    };
-
-    let v_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
-    v_tunnel.fill(map);
-
-    let (bottom_y, top_y) = (new_center.y, new_center.y + 1);
-    let (left_x, right_x) = if new_center.x < prev_center.x {
-        (new_center.x, prev_center.x)
-    } else {
-        (prev_center.x, new_center.x)
-    };
-
-    let h_tunnel = Room::new(left_x, right_x, bottom_y, top_y);
-    h_tunnel.fill(map);
-
-    if !h_tunnel.intersects_with_as_tunnels(&v_tunnel) {
-        let h_tunnel = Room::new(left_x, right_x + 1, bottom_y, top_y);
-        h_tunnel.fill(map);
-    }
-}
-
-fn distance_diff(room1: &Room, room2: &Room) -> (i32, i32) {
-    let rlx = room1.right_x - room2.left_x;
-    let lrx = room1.left_x - room2.right_x;
-    let tby = room1.top_y - room2.bottom_y;
-    let bty = room1.bottom_y - room2.top_y;
+/// damage taken each time a unit in body armour steps into deep water
+const DROWNING_DAMAGE: i32 = 4;
+/// turns someone who nearly drowned stays stunned for
+const DROWNING_STUN_TURNS: i32 = 2;
+
+/// how long lava keeps burning whoever stepped in it, and the damage it deals each turn
+const BURNING_TURNS: i32 = 3;
+const BURNING_DAMAGE: i32 = 2;
+
+/// what the tile does to the unit that just stepped onto it; returns whether the unit fell into
+/// a chasm
+fn enter_tile(kind: TileKind, unit: &mut Unit, messages: &mut Vec<Message>) -> bool {
+    match kind {
+        TileKind::Lava => {
+            messages.push(Message::new(
+                format!("{} is burned by the lava", unit.name()),
+                color::ORANGE,
+            ));
+            unit.take_damage(kind.entry_damage(), messages);
+
+            if unit.is_alive() {
+                unit.add_effect(
+                    StatusEffect::new(EffectKind::Burning, BURNING_TURNS, BURNING_DAMAGE),
+                    messages,
+                );
+            }
 
-    let w = min(rlx, lrx);
-    let h = min(tby, bty);
+            false
+        }
 
-    (h.abs(), w.abs())
-}
+        TileKind::Water if unit.wears_body_armor() => {
+            messages.push(Message::new(
+                format!("{} is dragged under by heavy armour", unit.name()),
+                color::LIGHT_BLUE,
+            ));
+            unit.take_damage(DROWNING_DAMAGE, messages);
+
+            if unit.is_alive() {
+                unit.add_effect(
+                    StatusEffect::new(EffectKind::Stun, DROWNING_STUN_TURNS, 0),
+                    messages,
+                );
+            }
 
-fn find_nearest_room<'a>(rooms: &'a Vec<Room>, target_room: &'a Room) -> &'a Room {
-    let mut nearest = &rooms[0];
-    let (h, w) = distance_diff(target_room, &nearest);
+            false
+        }
 
-    for i in 1..rooms.len() {
-        let (nh, nw) = distance_diff(target_room, &rooms[i]);
+        TileKind::Chasm => true,
 
-        if nh < h || nw < w {
-            nearest = &rooms[i];
-        }
+        _ => false,
     }
-
-    nearest
 }
 
-// fn connect_rooms(rooms: &Vec<Room>, map: &mut Map) {
-//     for i in 1..rooms.len() {
-//         let slice = &rooms[i..].to_vec();
-
-//         let nearest = find_nearest_room(slice, &rooms[i - 1]);
-
-//         if rand::random() {
-//             v_h_tunnel(&rooms[i - 1].get_center(), &nearest.get_center(), map);
-//         } else {
-//             h_v_tunnel(&rooms[i].get_center(), &nearest.get_center(), map);
-//         }
-//     }
-// }
-
-fn spawn_monsters(room: &mut Room, room_number: u32) -> Vec<Unit> {
+fn spawn_monsters(
+    map: &Map,
+    room: &mut Room,
+    room_number: u32,
+    depth: u32,
+    templates: &MonsterTemplates,
+    rng: &mut ChaCha12Rng,
+) -> Vec<Unit> {
     let mut monsters: Vec<Unit> = vec![];
 
-    let count_of_monsters_in_room = rand::thread_rng().gen_range(0..MAX_ROOM_MONSTERS + 1);
+    // deeper levels are more crowded
+    let max_monsters = MAX_ROOM_MONSTERS + (depth as i32 - 1) / 2;
+
+    let count_of_monsters_in_room = rng.gen_range(0..max_monsters + 1);
     let mut index = 0;
 
     let mut monsters_coordinates: Vec<(i32, i32)> = vec![];
 
     while index < count_of_monsters_in_room {
-        let x = rand::thread_rng().gen_range(room.left_x..room.right_x);
-        let y = rand::thread_rng().gen_range(room.bottom_y..room.top_y);
+        let x = rng.gen_range(room.left_x..room.right_x);
+        let y = rng.gen_range(room.bottom_y..room.top_y);
+
+        // a spawn that lands on a hazard is dropped rather than retried
+        if !map.is_safe_spot(x, y) {
+            index += 1;
+
+            continue;
+        }
 
         let is_place_taken = monsters_coordinates
             .iter()
@@ -241,12 +174,16 @@ fn spawn_monsters(room: &mut Room, room_number: u32) -> Vec<Unit> {
         if !is_place_taken {
             index += 1;
 
-            let new_monster = if rand::random::<f32>() < 0.8 {
-                Unit::orc(x, y, room_number)
-            } else {
-                Unit::troll(x, y, room_number)
+            let Some(template) = templates.choose(depth, rng) else {
+                break;
             };
 
+            let mut new_monster = Unit::from_template(template, x, y, room_number);
+
+            if rng.gen() {
+                new_monster.set_state(MonsterState::Wandering);
+            }
+
             monsters_coordinates.push((x, y));
 
             monsters.push(new_monster);
@@ -256,94 +193,202 @@ fn spawn_monsters(room: &mut Room, room_number: u32) -> Vec<Unit> {
     monsters
 }
 
-fn generate_rooms(map: &mut Map) -> (Vec<Room>, Vec<Unit>) {
-    let mut index = 0;
-    let mut rooms: Vec<Room> = vec![];
-    let mut monsters: Vec<Unit> = vec![];
+/// place items on free tiles of the room, never under a monster
+fn spawn_items(map: &Map, room: &Room, monsters: &[Unit], rng: &mut ChaCha12Rng) -> Vec<Item> {
+    let mut items: Vec<Item> = vec![];
 
-    while index < MAX_ROOMS {
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE..ROOM_MAX_SIZE + 1);
-        let y = rand::thread_rng().gen_range(1..map.get_height() - h - 1);
-        let x = rand::thread_rng().gen_range(1..map.get_width() - w - 1);
+    let count_of_items_in_room = rng.gen_range(0..MAX_ROOM_ITEMS + 1);
+    let mut index = 0;
 
-        let mut new_room = Room::new(x, x + w, y, y + h);
+    while index < count_of_items_in_room {
+        let x = rng.gen_range(room.left_x..room.right_x);
+        let y = rng.gen_range(room.bottom_y..room.top_y);
 
-        let intersects = rooms
-            .iter()
-            .any(|room| room.intersects_with_as_rooms(&new_room));
+        if !map.is_safe_spot(x, y) {
+            index += 1;
 
-        if intersects {
             continue;
         }
 
-        if !rooms.is_empty() {
-            let nearest = find_nearest_room(&rooms, &new_room);
+        let is_place_taken = monsters.iter().any(|monster| {
+            let pos = monster.get_position();
 
-            if rand::random() {
-                v_h_tunnel(&new_room.get_center(), &nearest.get_center(), map);
-            } else {
-                h_v_tunnel(&new_room.get_center(), &nearest.get_center(), map);
-            }
+            pos.x == x && pos.y == y
+        }) || items.iter().any(|item| {
+            let pos = item.get_position();
+
+            pos.x == x && pos.y == y
+        });
+
+        if !is_place_taken {
+            index += 1;
 
-            let monsters_in_room = spawn_monsters(&mut new_room, index as u32);
+            let roll = rng.gen::<f32>();
+
+            let new_item = if roll < 0.37 {
+                Item::healing_potion(x, y)
+            } else if roll < 0.41 {
+                Item::haste_potion(x, y)
+            } else if roll < 0.45 {
+                Item::regeneration_potion(x, y)
+            } else if roll < 0.55 {
+                Item::lightning_scroll(x, y)
+            } else if roll < 0.58 {
+                Item::stun_scroll(x, y)
+            } else if roll < 0.66 {
+                Item::fire_bolt_scroll(x, y)
+            } else if roll < 0.74 {
+                Item::throwing_knife(x, y)
+            } else if roll < 0.8 {
+                Item::sword(x, y)
+            } else if roll < 0.83 {
+                Item::short_bow(x, y)
+            } else if roll < 0.88 {
+                Item::leather_armor(x, y)
+            } else if roll < 0.93 {
+                Item::shield(x, y)
+            } else if roll < 0.97 {
+                Item::helmet(x, y)
+            } else {
+                Item::ring_of_vitality(x, y)
+            };
 
-            monsters.extend(monsters_in_room);
+            items.push(new_item);
         }
+    }
 
-        new_room.fill(map);
+    items
+}
 
-        rooms.push(new_room);
+/// the text with its first letter in upper case
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
 
-        index += 1;
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
     }
-
-    (rooms, monsters)
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     tiles: Vec<Vec<Tile>>,
     width: i32,
     height: i32,
-    fov: FovMap,
-    prev_player_pos: Coordinates,
+    /// tiles in the player's field of view, indexed by `x + y * width`; not saved,
+    /// it is recomputed from tiles by `Map::restore` after loading
+    #[serde(skip)]
+    fov: Vec<bool>,
     prev_player_move: Coordinates,
     rooms: Vec<Room>,
     monsters: Vec<Unit>,
+    items: Vec<Item>,
     player: Unit,
+    /// the same generator as rand's `ChaCha12Rng`, whose state can be saved so that a loaded level
+    /// plays out as it would have without the save
+    rng: ChaCha12Rng,
+    #[serde(skip)]
+    messages: Vec<Message>,
+    /// the player stepped into a chasm, the game moves them to the level below
+    #[serde(skip)]
+    player_fell: bool,
+    depth: u32,
+    down_stairs: Coordinates,
+    up_stairs: Option<Coordinates>,
 }
 
 impl Map {
-    pub fn new(width: i32, height: i32) -> Self {
+    /// generate a new map; the same seed always yields the same rooms, tunnels and monsters
+    pub fn new(
+        width: i32,
+        height: i32,
+        seed: u64,
+        depth: u32,
+        templates: &MonsterTemplates,
+        generator: &dyn Generator,
+    ) -> Self {
         let tiles = vec![vec![Tile::wall(); height as usize]; width as usize];
 
         let mut map = Self {
             tiles,
             width,
             height,
-            fov: FovMap::new(width, height),
-            prev_player_pos: Coordinates { x: 0, y: 0 },
+            fov: vec![false; (width * height) as usize],
             prev_player_move: Coordinates { x: 0, y: 0 },
             rooms: vec![],
             monsters: vec![],
+            items: vec![],
             player: Unit::player(0, 0),
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            messages: vec![],
+            player_fell: false,
+            depth,
+            down_stairs: Coordinates { x: 0, y: 0 },
+            up_stairs: None,
         };
 
-        let (rooms, monsters) = generate_rooms(&mut map);
+        let mut rng = ChaCha12Rng::seed_from_u64(seed);
+
+        let mut rooms = generator.generate(&mut map, &mut rng);
+        let mut attempts = 1;
+
+        // a generator can come up empty on a small map or after unlucky rolls, it tries again
+        // from where the random numbers left off
+        while rooms.is_empty() && attempts < GENERATION_ATTEMPTS {
+            map.tiles = vec![vec![Tile::wall(); height as usize]; width as usize];
+            rooms = generator.generate(&mut map, &mut rng);
+            attempts += 1;
+        }
+
+        // when no room fits at all, the whole inside of the map becomes one
+        if rooms.is_empty() {
+            let room = Room::new(1, width - 1, 1, height - 1);
+
+            room.fill(&mut map);
+            rooms.push(room);
+        }
+
+        repair_connectivity(&mut map, &rooms, &mut rng);
+
+        // the player's room stays empty
+        for (index, room) in rooms.iter_mut().enumerate().skip(1) {
+            let monsters_in_room =
+                spawn_monsters(&map, room, index as u32, depth, templates, &mut rng);
+            let items_in_room = spawn_items(&map, room, &monsters_in_room, &mut rng);
+
+            map.monsters.extend(monsters_in_room);
+            map.items.extend(items_in_room);
+        }
 
         map.rooms.extend(rooms);
-        map.monsters.extend(monsters);
+
+        debug_assert!(
+            map.connectivity().is_connected(),
+            "a room is still unreachable"
+        );
 
         let spawn_point = map.get_spawn_point();
 
+        // the player arrives on the up stairs and leaves through the farthest generated room
+        map.down_stairs = map.rooms[map.rooms.len() - 1].get_center();
+
+        if depth > 1 {
+            map.up_stairs = Some(spawn_point);
+        }
+
         let player = Unit::player(spawn_point.x, spawn_point.y);
 
         map.player = player;
+        map.rng = rng;
 
         map
     }
 
+    /// which rooms and tiles can be walked to from the player's arrival room
+    pub fn connectivity(&self) -> ConnectivityReport {
+        check_connectivity(self, &self.rooms)
+    }
+
     pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile) -> bool {
         if x >= self.width || x < 0 || y >= self.height || y < 0 {
             return false;
@@ -355,85 +400,132 @@ impl Map {
     }
 
     pub fn get_tile(&self, x: i32, y: i32) -> Result<Tile, String> {
-        if x > self.width || x < 0 || y > self.height || y < 0 {
+        if x >= self.width || x < 0 || y >= self.height || y < 0 {
             Err("Invalid coordinates".into())
         } else {
             Ok(self.tiles[x as usize][y as usize])
         }
     }
 
-    pub fn set_fov(&mut self) {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let tile = self.tiles[x as usize][y as usize];
+    /// recompute the player's field of view and mark everything in it as explored
+    pub fn update_fov(&mut self) {
+        let tiles = &self.tiles;
 
-                self.fov
-                    .set(x, y, !tile.is_block_sight(), !tile.is_blocked());
+        self.fov = compute_fov(
+            self.player.get_position(),
+            TORCH_RADIUS,
+            (self.width, self.height),
+            |x, y| !tiles[x as usize][y as usize].is_block_sight(),
+        );
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if self.is_in_fov(x, y) {
+                    self.tiles[x as usize][y as usize].set_explored(true);
+                }
             }
         }
     }
 
-    pub fn render(&mut self, offscreen: &mut Offscreen) {
-        let player_position = self.player.get_position();
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return false;
+        }
 
-        if !player_position.is_equal(&self.prev_player_pos) {
-            self.prev_player_pos.x = player_position.x;
-            self.prev_player_pos.y = player_position.y;
+        self.fov[(x + y * self.width) as usize]
+    }
 
-            self.fov.compute_fov(
-                player_position.x,
-                player_position.y,
-                TORCH_RADIUS,
-                FOV_LIGHT_WALLS,
-                FOV_ALGO,
-            );
-        }
+    /// rebuild the state that is not stored in save files: the FOV is recomputed from tiles
+    pub fn restore(&mut self) {
+        self.update_fov();
+    }
 
-        dbg!();
+    pub fn render(&self, renderer: &mut dyn Renderer) {
         for x in 0..self.width {
             for y in 0..self.height {
-                let visible = self.fov.is_in_fov(x, y);
-                let tile = &mut self.tiles[x as usize][y as usize];
+                let visible = self.is_in_fov(x, y);
+                let tile = &self.tiles[x as usize][y as usize];
+
+                if tile.is_explored() {
+                    renderer.set_background(x, y, tile.kind().color(visible));
 
-                if visible {
-                    tile.set_explored(true);
+                    if let Some((char, color)) = tile.kind().glyph() {
+                        let color = if visible { color } else { color::GREY };
+
+                        renderer.put_char(x, y, char, color);
+                    }
                 }
+            }
+        }
+
+        for (stairs, char) in [(Some(self.down_stairs), '>'), (self.up_stairs, '<')] {
+            if let Some(stairs) = stairs {
+                let tile = self.tiles[stairs.x as usize][stairs.y as usize];
 
                 if tile.is_explored() {
-                    let color = match (visible, tile.blocked) {
-                        // outside of field of view:
-                        (false, true) => COLOR_DARK_WALL,
-                        (false, false) => COLOR_DARK_GROUND,
-                        // inside fov:
-                        (true, true) => COLOR_LIGHT_WALL,
-                        (true, false) => COLOR_LIGHT_GROUND,
+                    let color = if self.is_in_fov(stairs.x, stairs.y) {
+                        color::WHITE
+                    } else {
+                        color::GREY
                     };
 
-                    offscreen.set_char_background(x, y, color, BackgroundFlag::Set);
+                    renderer.put_char(stairs.x, stairs.y, char, color);
                 }
             }
         }
 
-        dbg!();
+        for item in &self.items {
+            let pos = item.get_position();
+
+            if self.is_in_fov(pos.x, pos.y) {
+                item.draw(renderer);
+            }
+        }
+
         for monster in &self.monsters {
             let pos = monster.get_position();
 
-            if self.fov.is_in_fov(pos.x, pos.y) {
-                monster.draw(offscreen);
+            if self.is_in_fov(pos.x, pos.y) {
+                monster.draw(renderer);
             }
         }
 
-        self.player.draw(offscreen);
+        self.player.draw(renderer);
+    }
+
+    pub fn player(&self) -> &Unit {
+        &self.player
+    }
+
+    /// put the given player on this level at `position`
+    pub fn place_player(&mut self, mut player: Unit, position: Coordinates) {
+        player.set_position(position);
+
+        self.player = player;
+        self.update_fov();
+    }
+
+    pub fn add_message(&mut self, message: Message) {
+        self.messages.push(message);
     }
 
-    pub fn get_player(&mut self) -> &mut Unit {
-        &mut self.player
+    /// drain the messages produced since the last call, to be handed over to the GUI log
+    pub fn take_messages(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.messages)
     }
 
     pub fn get_spawn_point(&self) -> Coordinates {
         self.rooms[0].get_center()
     }
 
+    pub fn get_down_stairs(&self) -> Coordinates {
+        self.down_stairs
+    }
+
+    pub fn get_up_stairs(&self) -> Option<Coordinates> {
+        self.up_stairs
+    }
+
     pub fn get_width(&self) -> i32 {
         self.width
     }
@@ -451,8 +543,14 @@ impl Map {
 
         let tile = self.get_tile(x, y);
 
+        if let Ok(tile) = tile {
+            if tile.kind() == TileKind::Door(Door::Closed) {
+                return (UnitActions::OpenDoor, 0);
+            }
+        }
+
         let is_tile_blocked = if let Ok(tile) = tile {
-            tile.blocked
+            tile.is_blocked()
         } else {
             false
         };
@@ -480,15 +578,30 @@ impl Map {
 
         match self.possible_action(next_x, next_y) {
             (UnitActions::Move, _) => {
+                let kind = self.tiles[next_x as usize][next_y as usize].kind();
+
                 self.player.r#move(x, y);
+                self.player.spend_energy(kind.move_cost());
                 self.prev_player_move.x = x;
                 self.prev_player_move.y = y;
 
+                self.player_fell = enter_tile(kind, &mut self.player, &mut self.messages);
+
                 true
             }
 
             (UnitActions::Attack, target_id) => {
-                self.player.attack(&mut self.monsters[target_id]);
+                self.player
+                    .attack(&mut self.monsters[target_id], &mut self.messages);
+                self.monsters[target_id].alert(*self.player.get_position());
+                self.player.spend_energy(ATTACK_COST);
+
+                true
+            }
+
+            (UnitActions::OpenDoor, _) => {
+                self.open_door(next_x, next_y);
+                self.player.spend_energy(MOVE_COST);
 
                 true
             }
@@ -497,36 +610,1289 @@ impl Map {
         }
     }
 
-    pub fn monsters_action(&mut self, user_action: UserActions) {
-        if self.player.is_alive() && user_action != UserActions::DidNotTakeTurn {
-            dbg!();
-            for i in 0..self.monsters.len() {
-                if !self.monsters[i].is_alive() {
+    /// whether a unit can be put on the tile: it is walkable, harmless and nobody stands there
+    pub fn is_safe_spot(&self, x: i32, y: i32) -> bool {
+        let is_safe_tile = self
+            .get_tile(x, y)
+            .is_ok_and(|tile| !tile.is_blocked() && !tile.kind().is_hazard());
+
+        is_safe_tile
+            && !self.monsters.iter().any(|monster| {
+                let pos = monster.get_position();
+
+                monster.is_blocks_point() && pos.x == x && pos.y == y
+            })
+    }
+
+    /// raise the player's stat if they have gathered enough experience for the next level
+    pub fn level_up_player(&mut self, stat: Stat) -> bool {
+        if !self.player.can_level_up() {
+            return false;
+        }
+
+        self.player.level_up(stat);
+
+        self.messages.push(Message::new(
+            format!(
+                "Your battle skills grow stronger! You reached level {}",
+                self.player.level()
+            ),
+            color::YELLOW,
+        ));
+
+        true
+    }
+
+    /// whether the player fell into a chasm since the last call
+    pub fn take_player_fell(&mut self) -> bool {
+        std::mem::take(&mut self.player_fell)
+    }
+
+    /// open the closed door at the given tile; the field of view changes right away
+    fn open_door(&mut self, x: i32, y: i32) {
+        self.tiles[x as usize][y as usize].set_door(Door::Open);
+        self.update_fov();
+    }
+
+    /// close the open doors next to the player that nothing stands in
+    pub fn close_doors(&mut self) -> bool {
+        let player_pos = *self.player.get_position();
+        let mut closed = 0;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let (x, y) = (player_pos.x + dx, player_pos.y + dy);
+
+                if !matches!(self.get_tile(x, y), Ok(tile) if tile.kind() == TileKind::Door(Door::Open))
+                {
                     continue;
                 }
 
-                let monster_pos = self.monsters[i].get_position();
+                let position = Coordinates { x, y };
 
-                if !self.fov.is_in_fov(monster_pos.x, monster_pos.y) {
-                    continue;
+                let is_occupied = player_pos.is_equal(&position)
+                    || self.monsters.iter().any(|monster| {
+                        monster.is_alive() && monster.get_position().is_equal(&position)
+                    })
+                    || self
+                        .items
+                        .iter()
+                        .any(|item| item.get_position().is_equal(&position));
+
+                if !is_occupied {
+                    self.tiles[x as usize][y as usize].set_door(Door::Closed);
+                    closed += 1;
                 }
+            }
+        }
+
+        if closed == 0 {
+            self.messages.push(Message::new(
+                "There is no open door here to close",
+                color::LIGHT_GREY,
+            ));
 
-                let player_pos = self.player.get_position();
-                let (distance_to_player, dx, dy) = self.monsters[i].monster_step(player_pos);
+            return false;
+        }
+
+        self.messages.push(Message::new(
+            if closed == 1 {
+                "You close the door"
+            } else {
+                "You close the doors"
+            },
+            color::LIGHT_GREY,
+        ));
+        self.player.spend_energy(MOVE_COST);
+        self.update_fov();
 
-                if distance_to_player >= 2.0 {
-                    let next_x = monster_pos.x + dx;
-                    let next_y = monster_pos.y + dy;
+        true
+    }
 
-                    let (possible_monster_action, _) = self.possible_action(next_x, next_y);
+    /// pick up the item the player is standing on
+    pub fn pick_up_item(&mut self) -> bool {
+        let player_pos = *self.player.get_position();
 
-                    if possible_monster_action == UnitActions::Move {
-                        self.monsters[i].r#move(dx, dy);
-                    }
+        let item_id = self
+            .items
+            .iter()
+            .position(|item| item.get_position().is_equal(&player_pos));
+
+        let Some(item_id) = item_id else {
+            self.messages.push(Message::new(
+                "There is nothing here to pick up",
+                color::LIGHT_GREY,
+            ));
+
+            return false;
+        };
+
+        let item = self.items.remove(item_id);
+        let name = item.name().clone();
+
+        match self.player.add_item(item) {
+            Ok(()) => {
+                self.messages.push(Message::new(
+                    format!("You picked up {}", name),
+                    color::GREEN,
+                ));
+                self.player.spend_energy(ITEM_COST);
+
+                true
+            }
+
+            Err(item) => {
+                self.items.insert(item_id, item);
+                self.messages.push(Message::new(
+                    format!("Your inventory is full, cannot pick up {}", name),
+                    color::RED,
+                ));
+
+                false
+            }
+        }
+    }
+
+    /// drop the item from the player's inventory onto the tile under the player
+    pub fn drop_item(&mut self, index: usize) -> bool {
+        if index >= self.player.inventory().len() {
+            return false;
+        }
+
+        let mut item = self.player.remove_item(index);
+        item.set_position(*self.player.get_position());
+
+        self.messages.push(Message::new(
+            format!("You dropped {}", item.name()),
+            color::YELLOW,
+        ));
+
+        self.items.push(item);
+        self.player.spend_energy(ITEM_COST);
+
+        true
+    }
+
+    /// use the item from the player's inventory, consuming it if it had an effect
+    pub fn use_item(&mut self, index: usize) -> bool {
+        let Some(item) = self.player.inventory().get(index) else {
+            return false;
+        };
+
+        let name = item.name().clone();
+
+        let used = match item.kind() {
+            ItemKind::Potion { heal } => {
+                if self.player.current_hp() == self.player.max_hp() {
+                    self.messages
+                        .push(Message::new("You are already at full health", color::RED));
+
+                    false
                 } else {
-                    self.monsters[i].attack(&mut self.player);
+                    self.player.heal(heal);
+                    self.messages.push(Message::new(
+                        "Your wounds start to feel better!",
+                        color::LIGHT_VIOLET,
+                    ));
+
+                    true
+                }
+            }
+
+            ItemKind::Elixir(effect) => {
+                self.player.add_effect(effect, &mut self.messages);
+
+                true
+            }
+
+            ItemKind::Scroll { damage, range } => match self.closest_monster(range) {
+                Some(monster_id) => {
+                    let monster = &mut self.monsters[monster_id];
+
+                    self.messages.push(Message::new(
+                        format!(
+                            "A lightning bolt strikes the {} with a loud thunder! The damage is {}",
+                            monster.name(),
+                            damage
+                        ),
+                        color::LIGHT_BLUE,
+                    ));
+
+                    let killed = monster.take_damage(damage, &mut self.messages);
+                    monster.alert(*self.player.get_position());
+
+                    if let Some(xp) = killed {
+                        self.player.gain_xp(xp, &mut self.messages);
+                    }
+
+                    true
+                }
+
+                None => {
+                    self.messages.push(Message::new(
+                        "No enemy is close enough to strike",
+                        color::RED,
+                    ));
+
+                    false
+                }
+            },
+
+            ItemKind::Spell { .. } | ItemKind::Curse { .. } | ItemKind::Thrown { .. } => {
+                self.messages.push(Message::new(
+                    format!("{} has to be aimed at a target", name),
+                    color::LIGHT_GREY,
+                ));
+
+                false
+            }
+
+            ItemKind::Equipment(equipment) => {
+                let replaced = self.player.equip(index);
+
+                if let Some(replaced) = replaced {
+                    self.messages.push(Message::new(
+                        format!("You take off {}", replaced),
+                        color::LIGHT_YELLOW,
+                    ));
+                }
+
+                self.messages.push(Message::new(
+                    format!("You equip {} on {}", name, equipment.slot.name()),
+                    color::LIGHT_GREEN,
+                ));
+
+                self.player.spend_energy(ITEM_COST);
+
+                // equipping moves the item out of the inventory by itself
+                return true;
+            }
+        };
+
+        if used {
+            self.player.remove_item(index);
+            self.player.spend_energy(ITEM_COST);
+        }
+
+        used
+    }
+
+    /// cast the spell from the player's inventory at `target`
+    pub fn use_item_on(&mut self, index: usize, target: Coordinates) -> bool {
+        let Some(item) = self.player.inventory().get(index) else {
+            return false;
+        };
+
+        let (damage, range) = match item.kind() {
+            ItemKind::Spell { damage, range } => (damage, range),
+            ItemKind::Curse { effect, range } => return self.curse(index, effect, range, target),
+            _ => return self.use_item(index),
+        };
+
+        let player_pos = *self.player.get_position();
+        let impact = self
+            .line_of_fire(&player_pos, &target, range)
+            .last()
+            .copied();
+
+        match impact.and_then(|impact| self.monster_at(&impact)) {
+            Some(monster_id) => {
+                let monster = &mut self.monsters[monster_id];
+
+                self.messages.push(Message::new(
+                    format!(
+                        "A fire bolt engulfs the {}! The damage is {}",
+                        monster.name(),
+                        damage
+                    ),
+                    color::LIGHT_RED,
+                ));
+
+                let killed = monster.take_damage(damage, &mut self.messages);
+                monster.alert(player_pos);
+
+                if let Some(xp) = killed {
+                    self.player.gain_xp(xp, &mut self.messages);
                 }
             }
+
+            None => self.messages.push(Message::new(
+                "The fire bolt hits nothing and fizzles out",
+                color::LIGHT_GREY,
+            )),
+        }
+
+        self.player.remove_item(index);
+        self.player.spend_energy(ITEM_COST);
+
+        true
+    }
+
+    /// cast the curse from the player's inventory at `target`, putting `effect` on whoever it hits
+    fn curse(
+        &mut self,
+        index: usize,
+        effect: StatusEffect,
+        range: i32,
+        target: Coordinates,
+    ) -> bool {
+        let player_pos = *self.player.get_position();
+        let impact = self
+            .line_of_fire(&player_pos, &target, range)
+            .last()
+            .copied();
+
+        match impact.and_then(|impact| self.monster_at(&impact)) {
+            Some(monster_id) => {
+                let monster = &mut self.monsters[monster_id];
+
+                monster.add_effect(effect, &mut self.messages);
+                monster.alert(player_pos);
+            }
+
+            None => self.messages.push(Message::new(
+                "The curse hits nothing and fades away",
+                color::LIGHT_GREY,
+            )),
+        }
+
+        self.player.remove_item(index);
+        self.player.spend_energy(ITEM_COST);
+
+        true
+    }
+
+    /// throw the item from the player's inventory at `target`, it lands where it stops
+    pub fn throw_item(&mut self, index: usize, target: Coordinates) -> bool {
+        let Some(item) = self.player.inventory().get(index) else {
+            return false;
+        };
+
+        let ItemKind::Thrown { damage, range } = item.kind() else {
+            self.messages.push(Message::new(
+                format!("{} is not made for throwing", item.name()),
+                color::LIGHT_GREY,
+            ));
+
+            return false;
+        };
+
+        let mut item = self.player.remove_item(index);
+        let player_pos = *self.player.get_position();
+        let impact = self
+            .line_of_fire(&player_pos, &target, range)
+            .last()
+            .copied()
+            .unwrap_or(player_pos);
+
+        match self.monster_at(&impact) {
+            Some(monster_id) => {
+                self.player.attack_with(
+                    &mut self.monsters[monster_id],
+                    damage,
+                    item.name(),
+                    &mut self.messages,
+                );
+                self.monsters[monster_id].alert(player_pos);
+            }
+
+            None => self.messages.push(Message::new(
+                format!("You throw {}", item.name()),
+                color::LIGHT_GREY,
+            )),
+        }
+
+        item.set_position(impact);
+        self.items.push(item);
+        self.player.spend_energy(ATTACK_COST);
+
+        true
+    }
+
+    /// shoot at `target` with the ranged weapon the player wears
+    pub fn fire(&mut self, target: Coordinates) -> bool {
+        let range = self.player.range();
+
+        if range == 0 {
+            self.messages.push(Message::new(
+                "You have no ranged weapon equipped",
+                color::LIGHT_GREY,
+            ));
+
+            return false;
+        }
+
+        let player_pos = *self.player.get_position();
+        let impact = self
+            .line_of_fire(&player_pos, &target, range)
+            .last()
+            .copied();
+
+        match impact.and_then(|impact| self.monster_at(&impact)) {
+            Some(monster_id) => {
+                self.player
+                    .shoot(&mut self.monsters[monster_id], &mut self.messages);
+                self.monsters[monster_id].alert(player_pos);
+            }
+
+            None => self
+                .messages
+                .push(Message::new("Your arrow hits nothing", color::LIGHT_GREY)),
+        }
+
+        self.player.spend_energy(ATTACK_COST);
+
+        true
+    }
+
+    /// tiles a projectile flies over from `from` towards `to`, at most `range` of them. It stops
+    /// on the first unit in its way or before the first tile it cannot pass, so the last tile is
+    /// where it lands
+    pub fn line_of_fire(
+        &self,
+        from: &Coordinates,
+        to: &Coordinates,
+        range: i32,
+    ) -> Vec<Coordinates> {
+        let mut tiles = vec![];
+
+        for point in line(from, to).into_iter().take(range.max(0) as usize) {
+            if self
+                .get_tile(point.x, point.y)
+                .map_or(true, Tile::is_blocked)
+            {
+                break;
+            }
+
+            tiles.push(point);
+
+            if self.player.get_position().is_equal(&point) || self.monster_at(&point).is_some() {
+                break;
+            }
+        }
+
+        tiles
+    }
+
+    /// the living monster standing on the tile
+    fn monster_at(&self, position: &Coordinates) -> Option<usize> {
+        self.monsters.iter().position(|monster| {
+            monster.is_blocks_point() && monster.get_position().is_equal(position)
+        })
+    }
+
+    /// what the player can tell about the tile for the look mode: what the tile is and, while it
+    /// is in view, who and what is on it. `None` for tiles the player has never seen
+    pub fn describe(&self, x: i32, y: i32) -> Option<Vec<Message>> {
+        let tile = self.get_tile(x, y).ok().filter(|tile| tile.is_explored())?;
+        let position = Coordinates { x, y };
+        let visible = self.is_in_fov(x, y);
+        let ground = self.ground_name(&position, tile);
+
+        let mut lines = vec![if visible {
+            Message::new(format!("You see {}", ground), color::WHITE)
+        } else {
+            Message::new(format!("You remember {}", ground), color::LIGHT_GREY)
+        }];
+
+        if !visible {
+            return Some(lines);
+        }
+
+        let units = std::iter::once(&self.player).chain(&self.monsters);
+
+        for unit in units.filter(|unit| unit.get_position() == &position) {
+            let color = if unit.is_alive() {
+                color::LIGHT_RED
+            } else {
+                color::LIGHT_GREY
+            };
+
+            lines.push(Message::new(capitalize(&unit.description()), color));
+        }
+
+        let items = self.items.iter();
+
+        for item in items.filter(|item| item.get_position() == &position) {
+            lines.push(Message::new(
+                format!("{} lies here", item.label()),
+                color::LIGHT_YELLOW,
+            ));
+        }
+
+        Some(lines)
+    }
+
+    /// short names of what is on the tile: the units and items while it is in view, otherwise
+    /// the tile itself. `None` for tiles the player has never seen
+    pub fn names_at(&self, x: i32, y: i32) -> Option<String> {
+        let tile = self.get_tile(x, y).ok().filter(|tile| tile.is_explored())?;
+        let position = Coordinates { x, y };
+        let mut names = vec![];
+
+        if self.is_in_fov(x, y) {
+            let units = std::iter::once(&self.player).chain(&self.monsters);
+            let items = self.items.iter();
+
+            names.extend(
+                units
+                    .filter(|unit| unit.get_position() == &position)
+                    .map(Unit::display_name),
+            );
+            names.extend(
+                items
+                    .filter(|item| item.get_position() == &position)
+                    .map(|item| item.name().clone()),
+            );
+        }
+
+        if names.is_empty() {
+            names.push(self.ground_name(&position, tile).to_string());
+        }
+
+        Some(capitalize(&names.join(", ")))
+    }
+
+    /// the stairs on the tile or else what the tile is
+    fn ground_name(&self, position: &Coordinates, tile: Tile) -> &'static str {
+        if position == &self.down_stairs {
+            "stairs leading down"
+        } else if Some(*position) == self.up_stairs {
+            "stairs leading up"
+        } else {
+            tile.kind().name()
+        }
+    }
+
+    /// whether the player knows the tile to be safe to walk on: explored, not a wall or a hazard
+    /// and with no monster in view standing on it. Closed doors are opened on the way
+    fn is_known_walkable(&self, x: i32, y: i32) -> bool {
+        let tile = self.tiles[x as usize][y as usize];
+
+        tile.is_explored()
+            && (!tile.is_blocked() || tile.is_door())
+            && !tile.kind().is_hazard()
+            && !(self.is_in_fov(x, y) && self.monster_at(&Coordinates { x, y }).is_some())
+    }
+
+    /// first step of the way to `target` over the tiles the player knows to be walkable. `None`
+    /// when the player knows no way there
+    fn travel_step(&self, target: &Coordinates) -> Option<(i32, i32)> {
+        let is_walkable = |x: i32, y: i32| self.is_known_walkable(x, y);
+
+        let on_map =
+            target.x >= 0 && target.x < self.width && target.y >= 0 && target.y < self.height;
+
+        if !on_map || !is_walkable(target.x, target.y) {
+            return None;
+        }
+
+        let from = self.player.get_position();
+        let limit = (self.width * self.height) as usize;
+        let path = find_path(from, target, (self.width, self.height), is_walkable, limit)?;
+
+        path.first().map(|step| (step.x - from.x, step.y - from.y))
+    }
+
+    /// take one step towards `target`
+    pub fn travel(&mut self, target: Coordinates) -> bool {
+        if self.player.get_position() == &target {
+            return false;
+        }
+
+        match self.travel_step(&target) {
+            Some((dx, dy)) => self.player_move_or_attack(dx, dy),
+            None => {
+                self.messages
+                    .push(Message::new("You know no way there", color::LIGHT_GREY));
+
+                false
+            }
+        }
+    }
+
+    /// first step downhill on a Dijkstra map whose goals are the known walkable tiles next to
+    /// unexplored ones. `None` once the player knows no way to anything unexplored
+    fn explore_step(&self) -> Option<(i32, i32)> {
+        let is_unexplored =
+            |x: i32, y: i32| self.get_tile(x, y).is_ok_and(|tile| !tile.is_explored());
+
+        let mut frontier = vec![];
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let next_to_unexplored = (-1..=1)
+                    .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+                    .any(|(dx, dy)| is_unexplored(x + dx, y + dy));
+
+                if next_to_unexplored && self.is_known_walkable(x, y) {
+                    frontier.push(Coordinates { x, y });
+                }
+            }
+        }
+
+        let distances = dijkstra_map(&frontier, (self.width, self.height), |x, y| {
+            self.is_known_walkable(x, y)
+                .then(|| self.tiles[x as usize][y as usize].kind().move_cost())
+        });
+
+        downhill_step(
+            &distances,
+            (self.width, self.height),
+            self.player.get_position(),
+        )
+    }
+
+    /// take one step towards the closest part of the level the player has not explored yet
+    pub fn explore(&mut self) -> bool {
+        match self.explore_step() {
+            Some((dx, dy)) => self.player_move_or_attack(dx, dy),
+            None => {
+                self.messages.push(Message::new(
+                    "There is nothing left to explore that you can reach",
+                    color::LIGHT_GREY,
+                ));
+
+                false
+            }
+        }
+    }
+
+    /// let a turn pass without doing anything
+    pub fn wait_turn(&mut self) -> bool {
+        self.player.spend_energy(WAIT_COST);
+
+        true
+    }
+
+    /// nap for a short while to regain health, unless there is nothing to heal or monsters are in
+    /// view
+    pub fn rest(&mut self) -> bool {
+        if !self.visible_monsters().is_empty() {
+            self.messages.push(Message::new(
+                "You cannot rest with monsters in view",
+                color::LIGHT_GREY,
+            ));
+
+            return false;
+        }
+
+        if self.player.current_hp() >= self.player.max_hp() {
+            self.messages
+                .push(Message::new("You are at full health", color::LIGHT_GREY));
+
+            return false;
+        }
+
+        self.player.spend_energy(REST_COST);
+
+        true
+    }
+
+    /// positions of the items in the player's field of view
+    pub fn visible_items(&self) -> Vec<Coordinates> {
+        self.items
+            .iter()
+            .map(|item| *item.get_position())
+            .filter(|pos| self.is_in_fov(pos.x, pos.y))
+            .collect()
+    }
+
+    /// indices of the living monsters in the player's field of view, they stay the same for as
+    /// long as the level exists
+    pub fn visible_monster_ids(&self) -> Vec<usize> {
+        self.monsters
+            .iter()
+            .enumerate()
+            .filter(|(_, monster)| {
+                let pos = monster.get_position();
+
+                monster.is_alive() && self.is_in_fov(pos.x, pos.y)
+            })
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// positions of the living monsters in the player's field of view, closest first
+    pub fn visible_monsters(&self) -> Vec<Coordinates> {
+        let mut monsters: Vec<&Unit> = self
+            .monsters
+            .iter()
+            .filter(|monster| {
+                let pos = monster.get_position();
+
+                monster.is_alive() && self.is_in_fov(pos.x, pos.y)
+            })
+            .collect();
+
+        monsters.sort_by(|a, b| {
+            let distance_a = self.player.distance_to(a.get_position());
+            let distance_b = self.player.distance_to(b.get_position());
+
+            distance_a.total_cmp(&distance_b)
+        });
+
+        monsters
+            .iter()
+            .map(|monster| *monster.get_position())
+            .collect()
+    }
+
+    /// take off the item worn by the player, putting it back into the inventory
+    pub fn unequip_item(&mut self, index: usize) -> bool {
+        let Some(item) = self.player.equipment().get(index) else {
+            return false;
+        };
+
+        let name = item.name().clone();
+
+        if !self.player.unequip(index) {
+            self.messages.push(Message::new(
+                format!("Your inventory is full, cannot take off {}", name),
+                color::RED,
+            ));
+
+            return false;
+        }
+
+        self.messages.push(Message::new(
+            format!("You take off {}", name),
+            color::LIGHT_YELLOW,
+        ));
+        self.player.spend_energy(ITEM_COST);
+
+        true
+    }
+
+    /// the closest living monster in the player's FOV within `range`
+    fn closest_monster(&self, range: i32) -> Option<usize> {
+        let mut closest: Option<(usize, f32)> = None;
+
+        for (id, monster) in self.monsters.iter().enumerate() {
+            let pos = monster.get_position();
+
+            if !monster.is_alive() || !self.is_in_fov(pos.x, pos.y) {
+                continue;
+            }
+
+            let distance = self.player.distance_to(pos);
+
+            let is_closer = match closest {
+                Some((_, closest_distance)) => distance < closest_distance,
+                None => true,
+            };
+
+            if distance <= range as f32 && is_closer {
+                closest = Some((id, distance));
+            }
+        }
+
+        closest.map(|(id, _)| id)
+    }
+
+    /// first step of the shortest path from `from` to `to` that goes around walls and units
+    pub fn path_step(
+        &self,
+        from: &Coordinates,
+        to: &Coordinates,
+        limit: usize,
+    ) -> Option<(i32, i32)> {
+        // closed doors are opened on the way
+        let is_walkable = |x: i32, y: i32| {
+            let tile = self.tiles[x as usize][y as usize];
+
+            (!tile.is_blocked() || tile.is_door())
+                && !tile.kind().is_hazard()
+                && !self.monsters.iter().any(|monster| {
+                    let pos = monster.get_position();
+
+                    monster.is_blocks_point() && pos.x == x && pos.y == y
+                })
+        };
+
+        let path = find_path(from, to, (self.width, self.height), is_walkable, limit)?;
+
+        path.first().map(|step| (step.x - from.x, step.y - from.y))
+    }
+
+    /// whether `to` is within `radius` of `from` and no sight-blocking tile stands in between
+    pub fn is_in_sight(&self, from: &Coordinates, to: &Coordinates, radius: i32) -> bool {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+
+        if dx.pow(2) + dy.pow(2) > radius.pow(2) {
+            return false;
+        }
+
+        line(from, to)
+            .iter()
+            .filter(|point| !point.is_equal(to))
+            .all(|point| !self.tiles[point.x as usize][point.y as usize].is_block_sight())
+    }
+
+    fn can_monster_move_to(&self, x: i32, y: i32) -> bool {
+        let (possible_action, _) = self.possible_action(x, y);
+
+        possible_action == UnitActions::Move
+            && !self.tiles[x as usize][y as usize].kind().is_hazard()
+            && !self.player.get_position().is_equal(&Coordinates { x, y })
+    }
+
+    /// move the monster by one step, or open the door it bumps into; returns whether it acted
+    fn monster_bump(&mut self, id: usize, dx: i32, dy: i32) -> bool {
+        let monster_pos = *self.monsters[id].get_position();
+        let (x, y) = (monster_pos.x + dx, monster_pos.y + dy);
+
+        if self.possible_action(x, y).0 == UnitActions::OpenDoor {
+            self.open_door(x, y);
+
+            if self.is_in_fov(x, y) {
+                self.messages.push(Message::new(
+                    format!("{} opens a door", self.monsters[id].name()),
+                    color::LIGHT_GREY,
+                ));
+            }
+
+            return true;
+        }
+
+        if self.can_monster_move_to(x, y) {
+            self.monsters[id].r#move(dx, dy);
+
+            return true;
+        }
+
+        false
+    }
+
+    /// step towards `target`, around obstacles when a path exists; returns whether the monster moved
+    fn monster_move_towards(&mut self, id: usize, target: &Coordinates) -> bool {
+        let monster_pos = *self.monsters[id].get_position();
+        let (_, dx, dy) = self.monsters[id].monster_step(target);
+
+        let (dx, dy) = self
+            .path_step(&monster_pos, target, MONSTER_PATH_LIMIT)
+            .unwrap_or((dx, dy));
+
+        self.monster_bump(id, dx, dy)
+    }
+
+    fn monster_wander(&mut self, id: usize) -> bool {
+        let dx = self.rng.gen_range(-1..=1);
+        let dy = self.rng.gen_range(-1..=1);
+
+        (dx, dy) != (0, 0) && self.monster_bump(id, dx, dy)
+    }
+
+    /// let the monster perceive and act once, returning the energy the action cost
+    fn monster_turn(&mut self, i: usize) -> i32 {
+        let monster_pos = *self.monsters[i].get_position();
+        let player_pos = *self.player.get_position();
+        let sight_radius = self.monsters[i].perception().sight_radius;
+
+        let seen_player = if self.is_in_sight(&monster_pos, &player_pos, sight_radius) {
+            Some(player_pos)
+        } else {
+            None
+        };
+
+        let wakes = self.rng.gen_bool(WAKE_UP_CHANCE);
+        let noticed = self.monsters[i].perceive(seen_player, wakes);
+
+        if noticed && self.is_in_fov(monster_pos.x, monster_pos.y) {
+            self.messages.push(Message::new(
+                format!("{} notices you!", self.monsters[i].name()),
+                color::LIGHT_ORANGE,
+            ));
+        }
+
+        let perception = *self.monsters[i].perception();
+
+        let moved = match perception.state {
+            MonsterState::Sleeping => false,
+
+            MonsterState::Wandering => self.monster_wander(i),
+
+            MonsterState::Hunting => {
+                let (distance_to_player, _, _) = self.monsters[i].monster_step(&player_pos);
+
+                let range = self.monsters[i].range();
+
+                // ranged attackers shoot whenever the player is in their line of fire
+                let can_shoot = range > 0
+                    && self.line_of_fire(&monster_pos, &player_pos, range).last()
+                        == Some(&player_pos);
+
+                if can_shoot && distance_to_player >= 2.0 {
+                    let name = self.monsters[i].name().clone();
+
+                    self.messages.push(Message::new(
+                        format!("{} shoots at you", name),
+                        color::LIGHT_RED,
+                    ));
+                    self.monsters[i].shoot(&mut self.player, &mut self.messages);
+
+                    return ATTACK_COST;
+                } else if distance_to_player >= 2.0 {
+                    self.monster_move_towards(i, &player_pos)
+                } else {
+                    self.monsters[i].attack(&mut self.player, &mut self.messages);
+
+                    return ATTACK_COST;
+                }
+            }
+
+            MonsterState::Searching => match perception.last_known_player_pos {
+                Some(target) if !target.is_equal(&monster_pos) => {
+                    self.monster_move_towards(i, &target)
+                }
+
+                Some(_) => {
+                    self.monsters[i].forget_player();
+
+                    false
+                }
+
+                None => self.monster_wander(i),
+            },
+        };
+
+        if moved {
+            let position = *self.monsters[i].get_position();
+            let kind = self.tiles[position.x as usize][position.y as usize].kind();
+
+            enter_tile(kind, &mut self.monsters[i], &mut self.messages);
+
+            kind.move_cost()
+        } else {
+            // a monster that sleeps or cannot move still spends its whole turn
+            WAIT_COST
+        }
+    }
+
+    /// energy scheduler: after the player spent energy on an action, every unit gains energy equal
+    /// to its speed each tick and monsters act whenever they have enough, until the player can act again.
+    /// Monsters are always processed in the same order, so turns are deterministic under a seed
+    pub fn monsters_action(&mut self) {
+        if !self.player.is_alive() {
+            return;
+        }
+
+        self.player.end_turn(&mut self.messages);
+
+        loop {
+            while self.player.is_alive() && !self.player.can_act() {
+                self.player.gain_energy();
+                self.player.regenerate();
+
+                for i in 0..self.monsters.len() {
+                    if !self.monsters[i].is_alive() {
+                        continue;
+                    }
+
+                    self.monsters[i].gain_energy();
+                    self.monsters[i].regenerate();
+
+                    while self.monsters[i].is_alive()
+                        && self.monsters[i].can_act()
+                        && self.player.is_alive()
+                    {
+                        let cost = if self.monsters[i].start_turn(&mut self.messages) {
+                            self.monster_turn(i)
+                        } else {
+                            WAIT_COST
+                        };
+
+                        self.monsters[i].spend_energy(cost);
+                        self.monsters[i].end_turn(&mut self.messages);
+                    }
+                }
+            }
+
+            // a turn the player loses to an effect passes without asking for a command
+            if !self.player.is_alive() || self.player.start_turn(&mut self.messages) {
+                break;
+            }
+
+            self.player.spend_energy(WAIT_COST);
+            self.player.end_turn(&mut self.messages);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::GeneratorKind;
+    use crate::unit::NORMAL_SPEED;
+
+    fn map(seed: u64) -> Map {
+        Map::new(
+            80,
+            50,
+            seed,
+            1,
+            &MonsterTemplates::bundled(),
+            GeneratorKind::Bsp.generator().as_ref(),
+        )
+    }
+
+    #[test]
+    fn an_idle_monster_acts_once_per_player_turn_at_the_same_speed() {
+        let mut map = map(3);
+        let player_pos = *map.player.get_position();
+        let id = map
+            .monsters
+            .iter()
+            .position(|monster| {
+                monster.speed() == NORMAL_SPEED && monster.distance_to(&player_pos) > 20.0
+            })
+            .expect("a monster at normal speed far from the player");
+
+        // searching the spot it stands on, it forgets it and then only wanders
+        let position = *map.monsters[id].get_position();
+        map.monsters[id].alert(position);
+        let search_turns = map.monsters[id].perception().search_turns;
+
+        for _ in 0..3 {
+            map.player.spend_energy(WAIT_COST);
+            map.monsters_action();
+        }
+
+        assert_eq!(map.monsters[id].perception().search_turns, search_turns - 3);
+    }
+
+    #[test]
+    fn the_same_seed_builds_the_same_level() {
+        for kind in [
+            GeneratorKind::RandomRooms,
+            GeneratorKind::Bsp,
+            GeneratorKind::Caves,
+        ] {
+            let build = |seed| {
+                Map::new(
+                    80,
+                    50,
+                    seed,
+                    2,
+                    &MonsterTemplates::bundled(),
+                    kind.generator().as_ref(),
+                )
+            };
+            let (first, second, other) = (build(42), build(42), build(43));
+
+            // tiles, rooms, monsters, items and stairs all end up in the serialized level
+            assert_eq!(
+                serde_json::to_string(&first).unwrap(),
+                serde_json::to_string(&second).unwrap(),
+                "{:?}",
+                kind
+            );
+            assert_ne!(
+                serde_json::to_string(&first.tiles).unwrap(),
+                serde_json::to_string(&other.tiles).unwrap(),
+                "{:?}",
+                kind
+            );
+        }
+    }
+
+    #[test]
+    fn a_cheaper_action_lets_the_player_act_more_often() {
+        let mut map = map(3);
+        let player_pos = *map.player.get_position();
+        let id = map
+            .monsters
+            .iter()
+            .position(|monster| {
+                monster.speed() == NORMAL_SPEED && monster.distance_to(&player_pos) > 20.0
+            })
+            .expect("a monster at normal speed far from the player");
+        let position = *map.monsters[id].get_position();
+        map.monsters[id].alert(position);
+        let search_turns = map.monsters[id].perception().search_turns;
+
+        // resting costs half a turn, so the player rests twice for every monster turn
+        for _ in 0..4 {
+            map.player.spend_energy(REST_COST);
+            map.monsters_action();
+        }
+
+        assert_eq!(map.monsters[id].perception().search_turns, search_turns - 2);
+
+        // attacking costs more than a turn, so the monster gets in an extra turn now and then
+        for _ in 0..5 {
+            map.player.spend_energy(ATTACK_COST);
+            map.monsters_action();
+        }
+
+        assert_eq!(map.monsters[id].perception().search_turns, search_turns - 8);
+    }
+
+    fn effect_kinds(unit: &Unit) -> Vec<EffectKind> {
+        unit.effects().iter().map(|effect| effect.kind).collect()
+    }
+
+    /// an open field without monsters, with the player at (2, 2) wearing `weapon`
+    fn shooting_range(weapon: Option<Item>) -> Map {
+        let mut map = Map::new(
+            30,
+            5,
+            1,
+            1,
+            &MonsterTemplates::default(),
+            GeneratorKind::Bsp.generator().as_ref(),
+        );
+
+        for x in 0..30 {
+            for y in 0..5 {
+                map.set_tile(x, y, Tile::empty());
+            }
+        }
+
+        let mut player = Unit::player(0, 0);
+
+        if let Some(weapon) = weapon {
+            player.add_item(weapon).unwrap();
+            player.equip(0);
+        }
+
+        map.place_player(player, Coordinates { x: 2, y: 2 });
+        map
+    }
+
+    /// an orc standing at `x` in the row of the player
+    fn add_orc(field: &mut Map, x: i32) -> usize {
+        let mut orc = map(3)
+            .monsters
+            .into_iter()
+            .find(|monster| monster.name() == "Orc")
+            .expect("an orc");
+        orc.set_position(Coordinates { x, y: 2 });
+
+        field.monsters.push(orc);
+        field.monsters.len() - 1
+    }
+
+    #[test]
+    fn a_shot_stops_at_the_first_wall() {
+        let mut map = shooting_range(Some(Item::short_bow(0, 0)));
+        map.set_tile(6, 2, Tile::wall());
+        let orc = add_orc(&mut map, 8);
+        let player_pos = *map.player.get_position();
+
+        let tiles = map.line_of_fire(&player_pos, &Coordinates { x: 12, y: 2 }, 10);
+
+        assert_eq!(tiles.last(), Some(&Coordinates { x: 5, y: 2 }));
+        assert!(map.fire(Coordinates { x: 8, y: 2 }));
+        assert_eq!(map.monsters[orc].current_hp(), map.monsters[orc].max_hp());
+    }
+
+    #[test]
+    fn a_shot_stops_at_the_first_unit() {
+        let mut map = shooting_range(Some(Item::short_bow(0, 0)));
+        let front = add_orc(&mut map, 5);
+        let back = add_orc(&mut map, 7);
+        let player_pos = *map.player.get_position();
+
+        let tiles = map.line_of_fire(&player_pos, &Coordinates { x: 12, y: 2 }, 10);
+
+        assert_eq!(tiles.last(), Some(&Coordinates { x: 5, y: 2 }));
+        assert!(map.fire(Coordinates { x: 7, y: 2 }));
+
+        // the bow adds to the shot what it would not add to a melee hit
+        let shot = map.player.ranged_damage();
+        assert!(shot > map.player.damage());
+        assert_eq!(
+            map.monsters[front].current_hp(),
+            map.monsters[front].max_hp() - shot
+        );
+        assert_eq!(map.monsters[back].current_hp(), map.monsters[back].max_hp());
+    }
+
+    #[test]
+    fn a_shot_is_cut_off_at_its_range() {
+        let mut map = shooting_range(Some(Item::short_bow(0, 0)));
+        let player_pos = *map.player.get_position();
+        let range = map.player.range();
+        let orc = add_orc(&mut map, player_pos.x + range + 1);
+
+        let tiles = map.line_of_fire(&player_pos, &Coordinates { x: 25, y: 2 }, 4);
+
+        assert_eq!(tiles.len(), 4);
+        assert_eq!(tiles.last(), Some(&Coordinates { x: 6, y: 2 }));
+
+        assert!(map.fire(*map.monsters[orc].get_position()));
+        assert_eq!(map.monsters[orc].current_hp(), map.monsters[orc].max_hp());
+    }
+
+    #[test]
+    fn firing_without_a_ranged_weapon_takes_no_turn() {
+        let mut map = shooting_range(Some(Item::sword(0, 0)));
+        let orc = add_orc(&mut map, 5);
+
+        assert!(!map.fire(Coordinates { x: 5, y: 2 }));
+        assert!(map.player.can_act());
+        assert_eq!(map.monsters[orc].current_hp(), map.monsters[orc].max_hp());
+    }
+
+    #[test]
+    fn lava_sets_whoever_steps_in_on_fire() {
+        let mut player = Unit::player(0, 0);
+        let mut messages = vec![];
+
+        enter_tile(TileKind::Lava, &mut player, &mut messages);
+
+        assert_eq!(effect_kinds(&player), [EffectKind::Burning]);
+
+        let hp = player.current_hp();
+        player.end_turn(&mut messages);
+
+        assert_eq!(player.current_hp(), hp - BURNING_DAMAGE);
+    }
+
+    #[test]
+    fn deep_water_stuns_whoever_is_dragged_under_by_armour() {
+        let mut player = Unit::player(0, 0);
+        let mut messages = vec![];
+
+        enter_tile(TileKind::Water, &mut player, &mut messages);
+        assert!(player.effects().is_empty());
+
+        player.add_item(Item::leather_armor(0, 0)).unwrap();
+        player.equip(0);
+        enter_tile(TileKind::Water, &mut player, &mut messages);
+
+        assert_eq!(effect_kinds(&player), [EffectKind::Stun]);
+        assert!(!player.start_turn(&mut messages));
+    }
+
+    #[test]
+    fn small_maps_still_get_a_room_for_the_player() {
+        for kind in [
+            GeneratorKind::RandomRooms,
+            GeneratorKind::Bsp,
+            GeneratorKind::Caves,
+        ] {
+            for (width, height) in [(4, 4), (8, 6), (12, 12)] {
+                let map = Map::new(
+                    width,
+                    height,
+                    1,
+                    1,
+                    &MonsterTemplates::bundled(),
+                    kind.generator().as_ref(),
+                );
+                let player = map.player().get_position();
+
+                assert!(!map.rooms.is_empty());
+                assert!(
+                    map.is_safe_spot(player.x, player.y),
+                    "{:?} {}x{}",
+                    kind,
+                    width,
+                    height
+                );
+            }
         }
     }
 }
@@ -0,0 +1,268 @@
+use std::fs;
+
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::Deserialize;
+
+use crate::{color::Color, effect::StatusEffect, unit::AI};
+
+pub const MONSTERS_PATH: &str = "data/monsters.json";
+
+/// description of a kind of monster, as written by designers in the monsters data file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub max_hp: i32,
+    pub defense: i32,
+    pub damage: i32,
+    pub speed: i32,
+    pub sight_radius: i32,
+    pub ai: AI,
+    /// how far a monster with the `Ranged` ai shoots
+    #[serde(default)]
+    pub range: i32,
+    /// effect its attacks put on whoever they damage
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+    /// experience the player gains for killing it
+    pub xp: i32,
+    /// relative chance to be picked among the templates allowed at a depth, at `min_depth`
+    pub spawn_weight: u32,
+    /// how much the weight changes with every level below `min_depth`
+    #[serde(default)]
+    pub weight_per_depth: i32,
+    /// weight at which the change per depth stops
+    #[serde(default)]
+    pub weight_limit: Option<u32>,
+    pub min_depth: u32,
+    pub max_depth: u32,
+}
+
+impl MonsterTemplate {
+    fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".into());
+        }
+
+        if self.ai == AI::Player {
+            return Err("ai cannot be Player".into());
+        }
+
+        if self.max_hp <= 0 {
+            return Err(format!("max_hp must be positive, got {}", self.max_hp));
+        }
+
+        if self.speed <= 0 {
+            return Err(format!("speed must be positive, got {}", self.speed));
+        }
+
+        if self.sight_radius < 0 {
+            return Err(format!(
+                "sight_radius must not be negative, got {}",
+                self.sight_radius
+            ));
+        }
+
+        if self.ai == AI::Ranged && self.range <= 0 {
+            return Err(format!(
+                "range must be positive for the Ranged ai, got {}",
+                self.range
+            ));
+        }
+
+        if self.ai != AI::Ranged && self.range != 0 {
+            return Err(format!(
+                "range is only used by the Ranged ai, got {} for {:?}",
+                self.range, self.ai
+            ));
+        }
+
+        if let Some(effect) = &self.on_hit {
+            effect
+                .validate()
+                .map_err(|err| format!("on_hit: {}", err))?;
+        }
+
+        if self.xp < 0 {
+            return Err(format!("xp must not be negative, got {}", self.xp));
+        }
+
+        if self.spawn_weight == 0 {
+            return Err("spawn_weight must be positive".into());
+        }
+
+        match self.weight_limit {
+            Some(limit) if self.weight_per_depth > 0 && limit < self.spawn_weight => {
+                return Err(format!(
+                    "weight_limit {} is below spawn_weight {} while the weight grows with depth",
+                    limit, self.spawn_weight
+                ));
+            }
+            Some(limit) if self.weight_per_depth < 0 && limit > self.spawn_weight => {
+                return Err(format!(
+                    "weight_limit {} is above spawn_weight {} while the weight shrinks with depth",
+                    limit, self.spawn_weight
+                ));
+            }
+            Some(_) if self.weight_per_depth == 0 => {
+                return Err("weight_limit is only used with weight_per_depth".into());
+            }
+            _ => {}
+        }
+
+        if self.min_depth == 0 || self.min_depth > self.max_depth {
+            return Err(format!(
+                "depth range {}..={} is invalid, min_depth must be at least 1 and not above max_depth",
+                self.min_depth, self.max_depth
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// relative chance to be picked at `depth`, 0 where it does not spawn
+    fn weight_at(&self, depth: u32) -> u32 {
+        if depth < self.min_depth || depth > self.max_depth {
+            return 0;
+        }
+
+        let weight = self.spawn_weight as i64
+            + self.weight_per_depth as i64 * (depth - self.min_depth) as i64;
+
+        let weight = match self.weight_limit {
+            Some(limit) if self.weight_per_depth > 0 => weight.min(limit as i64),
+            Some(limit) => weight.max(limit as i64),
+            None => weight,
+        };
+
+        weight.clamp(0, u32::MAX as i64) as u32
+    }
+}
+
+/// all monster templates, used by the spawner as a weighted table per depth
+#[derive(Clone, Debug, Default)]
+pub struct MonsterTemplates {
+    templates: Vec<MonsterTemplate>,
+}
+
+impl MonsterTemplates {
+    pub fn new(templates: Vec<MonsterTemplate>) -> Result<Self, String> {
+        if templates.is_empty() {
+            return Err("at least one monster must be defined".into());
+        }
+
+        for (index, template) in templates.iter().enumerate() {
+            template
+                .validate()
+                .map_err(|err| format!("monster #{} ({:?}): {}", index + 1, template.name, err))?;
+
+            if templates[..index]
+                .iter()
+                .any(|other| other.name == template.name)
+            {
+                return Err(format!(
+                    "monster #{} ({:?}): name is already used by another monster",
+                    index + 1,
+                    template.name
+                ));
+            }
+        }
+
+        Ok(Self { templates })
+    }
+
+    /// the templates shipped with the game, for tests
+    #[cfg(test)]
+    pub fn bundled() -> Self {
+        let templates = serde_json::from_str(include_str!("../data/monsters.json")).unwrap();
+
+        Self::new(templates).unwrap()
+    }
+
+    /// read and validate the templates from a JSON file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+
+        let templates: Vec<MonsterTemplate> =
+            serde_json::from_str(&data).map_err(|err| format!("{}: {}", path, err))?;
+
+        Self::new(templates).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    /// pick a template allowed at `depth`, with probability proportional to its weight there
+    pub fn choose(&self, depth: u32, rng: &mut ChaCha12Rng) -> Option<&MonsterTemplate> {
+        let total: u32 = self
+            .templates
+            .iter()
+            .map(|template| template.weight_at(depth))
+            .sum();
+
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.gen_range(0..total);
+
+        for template in &self.templates {
+            let weight = template.weight_at(depth);
+
+            if roll < weight {
+                return Some(template);
+            }
+
+            roll -= weight;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str) -> MonsterTemplate {
+        MonsterTemplates::bundled()
+            .templates
+            .into_iter()
+            .find(|template| template.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn trolls_get_more_common_with_depth_up_to_a_cap() {
+        let (orc, troll) = (template("Orc"), template("Troll"));
+
+        // the share of trolls among orcs and trolls grows by 5% a level from 20% to 60%
+        for depth in 1..=20 {
+            let orcs = orc.weight_at(depth);
+            let trolls = troll.weight_at(depth);
+            let expected = (20 + 5 * (depth - 1)).min(60);
+
+            assert_eq!(trolls * 100 / (orcs + trolls), expected, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn templates_do_not_spawn_outside_their_depths() {
+        let archer = template("Goblin archer");
+
+        assert_eq!(archer.weight_at(1), 0);
+        assert_eq!(archer.weight_at(2), archer.spawn_weight);
+        assert_eq!(archer.weight_at(101), 0);
+    }
+
+    #[test]
+    fn a_weight_limit_against_the_change_per_depth_is_refused() {
+        let mut troll = template("Troll");
+        troll.weight_limit = Some(10);
+
+        assert!(MonsterTemplates::new(vec![troll.clone()]).is_err());
+
+        troll.weight_per_depth = 0;
+        assert!(MonsterTemplates::new(vec![troll]).is_err());
+    }
+}
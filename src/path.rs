@@ -0,0 +1,309 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::unit::Coordinates;
+
+const DIRECTIONS: [(i32, i32); 8] = [
+    (0, -1),
+    (0, 1),
+    (-1, 0),
+    (1, 0),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+/// number of moves between two points when diagonal steps are allowed
+fn chebyshev(from: &Coordinates, to: &Coordinates) -> i32 {
+    (from.x - to.x).abs().max((from.y - to.y).abs())
+}
+
+/// A* search on a `width` x `height` grid with eight-directional movement.
+///
+/// `is_walkable` decides which tiles can be entered, the goal itself is always considered walkable
+/// so that a path can lead up to a unit standing on it. The search gives up after expanding
+/// `limit` tiles. The returned path excludes `start` and ends with `goal`.
+pub fn find_path(
+    start: &Coordinates,
+    goal: &Coordinates,
+    (width, height): (i32, i32),
+    is_walkable: impl Fn(i32, i32) -> bool,
+    limit: usize,
+) -> Option<Vec<Coordinates>> {
+    let index = |x: i32, y: i32| (x + y * width) as usize;
+    let is_on_grid = |x: i32, y: i32| x >= 0 && x < width && y >= 0 && y < height;
+
+    if !is_on_grid(start.x, start.y) || !is_on_grid(goal.x, goal.y) {
+        return None;
+    }
+
+    let mut cost = vec![i32::MAX; (width * height) as usize];
+    let mut came_from: Vec<Option<usize>> = vec![None; (width * height) as usize];
+    let mut open = BinaryHeap::new();
+
+    cost[index(start.x, start.y)] = 0;
+    open.push(Reverse((chebyshev(start, goal), 0, start.x, start.y)));
+
+    let mut expanded = 0;
+
+    while let Some(Reverse((_, current_cost, x, y))) = open.pop() {
+        if x == goal.x && y == goal.y {
+            let mut path = vec![];
+            let mut current = index(x, y);
+
+            while let Some(previous) = came_from[current] {
+                path.push(Coordinates {
+                    x: current as i32 % width,
+                    y: current as i32 / width,
+                });
+                current = previous;
+            }
+
+            path.reverse();
+
+            return Some(path);
+        }
+
+        // stale entry, the tile was reached cheaper since it was queued
+        if current_cost > cost[index(x, y)] {
+            continue;
+        }
+
+        expanded += 1;
+
+        if expanded > limit {
+            return None;
+        }
+
+        for (dx, dy) in DIRECTIONS {
+            let (next_x, next_y) = (x + dx, y + dy);
+
+            if !is_on_grid(next_x, next_y) {
+                continue;
+            }
+
+            let is_goal = next_x == goal.x && next_y == goal.y;
+
+            if !is_goal && !is_walkable(next_x, next_y) {
+                continue;
+            }
+
+            let next_cost = current_cost + 1;
+            let next = index(next_x, next_y);
+            let next_point = Coordinates {
+                x: next_x,
+                y: next_y,
+            };
+
+            if next_cost < cost[next] {
+                cost[next] = next_cost;
+                came_from[next] = Some(index(x, y));
+
+                let estimate = next_cost + chebyshev(&next_point, goal);
+
+                open.push(Reverse((estimate, next_cost, next_x, next_y)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Dijkstra map on a `width` x `height` grid with eight-directional movement: for every tile,
+/// the cheapest way to the closest of `goals`. `cost` is the price of stepping onto a tile, or
+/// `None` for tiles that cannot be entered, including goals. Unreachable tiles are `None`
+pub fn dijkstra_map(
+    goals: &[Coordinates],
+    (width, height): (i32, i32),
+    cost: impl Fn(i32, i32) -> Option<i32>,
+) -> Vec<Option<i32>> {
+    let index = |x: i32, y: i32| (x + y * width) as usize;
+    let is_on_grid = |x: i32, y: i32| x >= 0 && x < width && y >= 0 && y < height;
+
+    let mut distances = vec![None; (width * height) as usize];
+    let mut open = BinaryHeap::new();
+
+    for goal in goals {
+        if is_on_grid(goal.x, goal.y) && cost(goal.x, goal.y).is_some() {
+            distances[index(goal.x, goal.y)] = Some(0);
+            open.push(Reverse((0, goal.x, goal.y)));
+        }
+    }
+
+    while let Some(Reverse((distance, x, y))) = open.pop() {
+        // stale entry, the tile was reached cheaper since it was queued
+        if distances[index(x, y)].is_some_and(|best| distance > best) {
+            continue;
+        }
+
+        // whoever stands next to this tile pays its cost to step onto it
+        let Some(step_cost) = cost(x, y) else {
+            continue;
+        };
+
+        for (dx, dy) in DIRECTIONS {
+            let (next_x, next_y) = (x + dx, y + dy);
+
+            if !is_on_grid(next_x, next_y) || cost(next_x, next_y).is_none() {
+                continue;
+            }
+
+            let next_distance = distance + step_cost;
+            let next = &mut distances[index(next_x, next_y)];
+
+            if next.is_none_or(|best| next_distance < best) {
+                *next = Some(next_distance);
+                open.push(Reverse((next_distance, next_x, next_y)));
+            }
+        }
+    }
+
+    distances
+}
+
+/// step from `from` to its neighbour lowest on the Dijkstra map, `None` when no neighbour is
+/// lower, like on a goal or a tile the map does not reach
+pub fn downhill_step(
+    distances: &[Option<i32>],
+    (width, height): (i32, i32),
+    from: &Coordinates,
+) -> Option<(i32, i32)> {
+    let index = |x: i32, y: i32| (x + y * width) as usize;
+    let is_on_grid = |x: i32, y: i32| x >= 0 && x < width && y >= 0 && y < height;
+
+    let mut lowest = distances[index(from.x, from.y)]?;
+    let mut step = None;
+
+    for (dx, dy) in DIRECTIONS {
+        let (x, y) = (from.x + dx, from.y + dy);
+
+        if !is_on_grid(x, y) {
+            continue;
+        }
+
+        if let Some(distance) = distances[index(x, y)] {
+            if distance < lowest {
+                lowest = distance;
+                step = Some((dx, dy));
+            }
+        }
+    }
+
+    step
+}
+
+/// tiles on the Bresenham line from `from` to `to`, excluding `from` and including `to`
+pub fn line(from: &Coordinates, to: &Coordinates) -> Vec<Coordinates> {
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+
+    let mut error = dx + dy;
+    let (mut x, mut y) = (from.x, from.y);
+    let mut points = vec![];
+
+    while x != to.x || y != to.y {
+        let double_error = error * 2;
+
+        if double_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+
+        if double_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+
+        points.push(Coordinates { x, y });
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: usize = 1000;
+
+    /// finds a path on a drawn grid from `S` to `G`, `#` is a wall and `o` a unit in the way
+    fn path_on(rows: &[&str]) -> Option<Vec<Coordinates>> {
+        let at = |wanted: char| {
+            rows.iter()
+                .enumerate()
+                .find_map(|(y, row)| {
+                    row.find(wanted).map(|x| Coordinates {
+                        x: x as i32,
+                        y: y as i32,
+                    })
+                })
+                .unwrap()
+        };
+        let size = (rows[0].len() as i32, rows.len() as i32);
+        let is_walkable =
+            |x: i32, y: i32| !matches!(rows[y as usize].as_bytes()[x as usize], b'#' | b'o');
+
+        find_path(&at('S'), &at('G'), size, is_walkable, LIMIT)
+    }
+
+    fn is_connected(start: Coordinates, path: &[Coordinates]) -> bool {
+        let mut previous = start;
+
+        path.iter().all(|point| {
+            let is_step = chebyshev(&previous, point) == 1;
+            previous = *point;
+            is_step
+        })
+    }
+
+    #[test]
+    fn a_path_goes_around_a_wall() {
+        let rows = [
+            ".....", //
+            "..#..", //
+            "S.#.G", //
+            "..#..", //
+            ".....",
+        ];
+        let path = path_on(&rows).unwrap();
+
+        assert_eq!(path.last(), Some(&Coordinates { x: 4, y: 2 }));
+        assert!(is_connected(Coordinates { x: 0, y: 2 }, &path));
+        assert!(path
+            .iter()
+            .all(|point| point.x != 2 || point.y == 0 || point.y == 4));
+        assert_eq!(path.len(), 4);
+    }
+
+    #[test]
+    fn a_path_goes_around_a_unit() {
+        let rows = [
+            "#####", //
+            "#...#", //
+            "#SoG#", //
+            "#...#", //
+            "#####",
+        ];
+        let path = path_on(&rows).unwrap();
+
+        assert_eq!(path.len(), 2);
+        assert!(is_connected(Coordinates { x: 1, y: 2 }, &path));
+        assert!(!path.contains(&Coordinates { x: 2, y: 2 }));
+        assert_eq!(path.last(), Some(&Coordinates { x: 3, y: 2 }));
+    }
+
+    #[test]
+    fn an_enclosed_goal_has_no_path() {
+        let rows = [
+            ".......", //
+            "S.###..", //
+            "..#G#..", //
+            "..###..", //
+            ".......",
+        ];
+
+        assert_eq!(path_on(&rows), None);
+    }
+}
@@ -0,0 +1,9 @@
+use crate::color::Color;
+
+/// drawing surface the game state renders itself onto; the game core only describes what goes
+/// where, the frontend decides how it ends up on screen
+pub trait Renderer {
+    fn put_char(&mut self, x: i32, y: i32, char: char, color: Color);
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color);
+}
@@ -0,0 +1,122 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{game::Game, monsters::MonsterTemplates};
+
+const SAVE_PATH: &str = "savegame.json";
+
+/// bumped whenever the layout of saved structures changes, older saves are refused
+const SAVE_VERSION: u32 = 13;
+
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u32,
+    game: &'a Game,
+}
+
+/// read first, so that saves from other versions are reported instead of failing to parse
+#[derive(Deserialize)]
+struct SaveVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct SaveFile {
+    game: Game,
+}
+
+pub fn save_exists() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+pub fn save_game(game: &Game) -> Result<(), String> {
+    let data = encode(game)?;
+
+    fs::write(SAVE_PATH, data).map_err(|err| err.to_string())
+}
+
+pub fn load_game(templates: MonsterTemplates) -> Result<Game, String> {
+    let data = fs::read_to_string(SAVE_PATH).map_err(|err| err.to_string())?;
+
+    decode(&data, templates)
+}
+
+fn encode(game: &Game) -> Result<String, String> {
+    let save = SaveFileRef {
+        version: SAVE_VERSION,
+        game,
+    };
+
+    serde_json::to_string(&save).map_err(|err| err.to_string())
+}
+
+fn decode(data: &str, templates: MonsterTemplates) -> Result<Game, String> {
+    let SaveVersion { version } = serde_json::from_str(data).map_err(|err| err.to_string())?;
+
+    if version != SAVE_VERSION {
+        return Err(format!(
+            "save file version {} is not supported (expected {})",
+            version, SAVE_VERSION
+        ));
+    }
+
+    let save: SaveFile = serde_json::from_str(data).map_err(|err| err.to_string())?;
+
+    let mut game = save.game;
+    game.restore(templates);
+
+    Ok(game)
+}
+
+/// permadeath: once the player is dead (or starts over) the save is gone
+pub fn delete_save() {
+    if save_exists() {
+        let _ = fs::remove_file(SAVE_PATH);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Command, Event};
+    use crate::generator::GeneratorKind;
+
+    fn texts(events: Vec<Event>) -> Vec<String> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Message(message) => Some(message.text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_loaded_game_plays_out_like_the_saved_one() {
+        let mut game = Game::new(80, 50, 7, GeneratorKind::Bsp, MonsterTemplates::bundled());
+        let data = encode(&game).unwrap();
+        let mut loaded = decode(&data, MonsterTemplates::bundled()).unwrap();
+
+        for _ in 0..100 {
+            assert_eq!(
+                texts(game.apply(Command::Wait)),
+                texts(loaded.apply(Command::Wait))
+            );
+        }
+
+        assert_eq!(encode(&game), encode(&loaded));
+    }
+
+    #[test]
+    fn saves_from_another_version_are_refused() {
+        let game = Game::new(80, 50, 7, GeneratorKind::Bsp, MonsterTemplates::bundled());
+        let data = encode(&game).unwrap().replacen(
+            &format!("\"version\":{}", SAVE_VERSION),
+            "\"version\":1",
+            1,
+        );
+
+        assert!(decode(&data, MonsterTemplates::bundled()).is_err());
+    }
+}
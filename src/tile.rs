@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::{self, Color},
+    unit::MOVE_COST,
+};
+
+const COLOR_DOOR: Color = Color {
+    r: 160,
+    g: 100,
+    b: 40,
+};
+
+const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
+const COLOR_LIGHT_WALL: Color = Color {
+    r: 130,
+    g: 110,
+    b: 50,
+};
+const COLOR_DARK_GROUND: Color = Color {
+    r: 50,
+    g: 50,
+    b: 150,
+};
+const COLOR_LIGHT_GROUND: Color = Color {
+    r: 200,
+    g: 180,
+    b: 50,
+};
+const COLOR_DARK_WATER: Color = Color { r: 0, g: 0, b: 90 };
+const COLOR_LIGHT_WATER: Color = Color {
+    r: 30,
+    g: 60,
+    b: 200,
+};
+const COLOR_DARK_LAVA: Color = Color { r: 90, g: 20, b: 0 };
+const COLOR_LIGHT_LAVA: Color = Color {
+    r: 220,
+    g: 70,
+    b: 0,
+};
+const COLOR_DARK_GRASS: Color = Color {
+    r: 20,
+    g: 70,
+    b: 40,
+};
+const COLOR_LIGHT_GRASS: Color = Color {
+    r: 90,
+    g: 170,
+    b: 40,
+};
+const COLOR_CHASM: Color = Color { r: 0, g: 0, b: 0 };
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Door {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TileKind {
+    Wall,
+    Floor,
+    Door(Door),
+    /// deep water, heavy armour drags whoever enters it under
+    Water,
+    Lava,
+    /// slows down whoever crosses it
+    Rubble,
+    /// tall grass, hides whatever is behind it
+    Grass,
+    /// whoever steps in falls to the next level
+    Chasm,
+}
+
+impl TileKind {
+    pub fn is_walkable(self) -> bool {
+        !matches!(self, TileKind::Wall | TileKind::Door(Door::Closed))
+    }
+
+    pub fn is_transparent(self) -> bool {
+        !matches!(
+            self,
+            TileKind::Wall | TileKind::Door(Door::Closed) | TileKind::Grass
+        )
+    }
+
+    /// energy spent to step onto the tile
+    pub fn move_cost(self) -> i32 {
+        match self {
+            TileKind::Water => MOVE_COST * 2,
+            TileKind::Rubble => MOVE_COST * 3 / 2,
+            _ => MOVE_COST,
+        }
+    }
+
+    /// damage taken by whoever steps onto the tile
+    pub fn entry_damage(self) -> i32 {
+        match self {
+            TileKind::Lava => 10,
+            _ => 0,
+        }
+    }
+
+    /// tiles that monsters keep away from and nothing is spawned on
+    pub fn is_hazard(self) -> bool {
+        self.entry_damage() > 0 || self == TileKind::Chasm
+    }
+
+    /// what the tile is called in the look mode
+    pub fn name(self) -> &'static str {
+        match self {
+            TileKind::Wall => "a stone wall",
+            TileKind::Floor => "the floor",
+            TileKind::Door(Door::Closed) => "a closed door",
+            TileKind::Door(Door::Open) => "an open door",
+            TileKind::Water => "deep water",
+            TileKind::Lava => "a pool of lava",
+            TileKind::Rubble => "rubble",
+            TileKind::Grass => "tall grass",
+            TileKind::Chasm => "a chasm",
+        }
+    }
+
+    /// character drawn on top of the background, if any
+    pub fn glyph(self) -> Option<(char, Color)> {
+        match self {
+            TileKind::Door(Door::Closed) => Some(('+', COLOR_DOOR)),
+            TileKind::Door(Door::Open) => Some(('\'', COLOR_DOOR)),
+            TileKind::Water => Some(('~', color::LIGHT_BLUE)),
+            TileKind::Lava => Some(('~', color::YELLOW)),
+            TileKind::Rubble => Some((':', color::LIGHT_GREY)),
+            TileKind::Grass => Some(('"', color::LIGHT_GREEN)),
+            TileKind::Chasm => Some((' ', color::BLACK)),
+            TileKind::Wall | TileKind::Floor => None,
+        }
+    }
+
+    /// background colour, `lit` inside the field of view
+    pub fn color(self, lit: bool) -> Color {
+        match (self, lit) {
+            (TileKind::Wall | TileKind::Door(Door::Closed), false) => COLOR_DARK_WALL,
+            (TileKind::Wall | TileKind::Door(Door::Closed), true) => COLOR_LIGHT_WALL,
+            (TileKind::Water, false) => COLOR_DARK_WATER,
+            (TileKind::Water, true) => COLOR_LIGHT_WATER,
+            (TileKind::Lava, false) => COLOR_DARK_LAVA,
+            (TileKind::Lava, true) => COLOR_LIGHT_LAVA,
+            (TileKind::Grass, false) => COLOR_DARK_GRASS,
+            (TileKind::Grass, true) => COLOR_LIGHT_GRASS,
+            (TileKind::Chasm, _) => COLOR_CHASM,
+            (_, false) => COLOR_DARK_GROUND,
+            (_, true) => COLOR_LIGHT_GROUND,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Tile {
+    kind: TileKind,
+    explored: bool,
+}
+
+impl Tile {
+    pub fn new(kind: TileKind) -> Self {
+        Tile {
+            kind,
+            explored: false,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Tile::new(TileKind::Floor)
+    }
+
+    pub fn wall() -> Self {
+        Tile::new(TileKind::Wall)
+    }
+
+    /// a closed door, it blocks movement and sight until it is opened
+    pub fn door() -> Self {
+        Tile::new(TileKind::Door(Door::Closed))
+    }
+
+    pub fn kind(self) -> TileKind {
+        self.kind
+    }
+
+    pub fn is_door(self) -> bool {
+        matches!(self.kind, TileKind::Door(_))
+    }
+
+    /// open or close the door, does nothing to tiles that are not doors
+    pub fn set_door(&mut self, door: Door) {
+        if self.is_door() {
+            self.kind = TileKind::Door(door);
+        }
+    }
+
+    pub fn set_explored(&mut self, value: bool) {
+        self.explored = value;
+    }
+
+    pub fn is_blocked(self) -> bool {
+        !self.kind.is_walkable()
+    }
+
+    pub fn is_block_sight(self) -> bool {
+        !self.kind.is_transparent()
+    }
+
+    pub fn is_explored(self) -> bool {
+        self.explored
+    }
+}
@@ -1,6 +1,45 @@
-use tcod::{colors, BackgroundFlag, Color, Console};
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq)]
+use crate::{
+    color::{self, Color},
+    effect::StatusEffect,
+    game::Message,
+    item::{Equipment, Item, Slot},
+    monsters::MonsterTemplate,
+    render::Renderer,
+};
+
+const PLAYER_INVENTORY_CAPACITY: usize = 26;
+
+/// experience needed to go from level `n` to `n + 1` is `LEVEL_UP_BASE + n * LEVEL_UP_FACTOR`
+const LEVEL_UP_BASE: i32 = 200;
+const LEVEL_UP_FACTOR: i32 = 150;
+
+pub const LEVEL_UP_MAX_HP: i32 = 20;
+pub const LEVEL_UP_DAMAGE: i32 = 1;
+pub const LEVEL_UP_DEFENSE: i32 = 1;
+
+/// energy a unit needs to act; every tick a unit gains energy equal to its speed
+pub const ACTION_ENERGY: i32 = 100;
+pub const NORMAL_SPEED: i32 = 10;
+
+/// energy each action costs, a unit at normal speed gets `ACTION_ENERGY` back every ten ticks
+pub const MOVE_COST: i32 = 100;
+/// swinging or shooting leaves a unit open for longer than a step does
+pub const ATTACK_COST: i32 = 120;
+pub const ITEM_COST: i32 = 100;
+/// a whole turn spent doing nothing, also what idle monsters pay
+pub const WAIT_COST: i32 = 100;
+/// resting is done in short naps so the player can react as soon as something happens
+pub const REST_COST: i32 = 50;
+
+/// turns a unit needs to regenerate all of its hit points, so tougher units heal faster
+const REGENERATION_TURNS: i32 = 300;
+
+/// turns a monster keeps searching after losing sight of the player
+const SEARCH_TURNS: i32 = 10;
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum UserActions {
     TookTurn,
     DidNotTakeTurn,
@@ -11,16 +50,56 @@ pub enum UserActions {
 pub enum UnitActions {
     Attack,
     Move,
+    OpenDoor,
     AFK,
 }
 
+/// stat the player raises on level up
 #[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stat {
+    MaxHp,
+    Damage,
+    Defense,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum AI {
     Basic,
+    /// shoots at the player whenever it has a clear line of fire within its range
+    Ranged,
     Player,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MonsterState {
+    Sleeping,
+    Wandering,
+    Hunting,
+    Searching,
+}
+
+/// what a unit can see and remembers about the player, independently of the player's own FOV
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Perception {
+    pub state: MonsterState,
+    pub sight_radius: i32,
+    pub last_known_player_pos: Option<Coordinates>,
+    /// turns left before a searching monster gives up and goes back to wandering
+    pub search_turns: i32,
+}
+
+impl Perception {
+    fn new(state: MonsterState, sight_radius: i32) -> Self {
+        Self {
+            state,
+            sight_radius,
+            last_known_player_pos: None,
+            search_turns: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Coordinates {
     pub x: i32,
     pub y: i32,
@@ -32,15 +111,18 @@ impl Coordinates {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct UnitStats {
     max_hp: i32,
     current_hp: i32,
     defense: i32,
     damage: i32,
+    speed: i32,
+    /// how far the unit can shoot without a ranged weapon, 0 if it cannot
+    range: i32,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Unit {
     position: Coordinates,
     char: char,
@@ -51,57 +133,32 @@ pub struct Unit {
     spawn_room: u32,
     stats: UnitStats,
     ai: AI,
+    inventory: Vec<Item>,
+    inventory_capacity: usize,
+    equipment: Vec<Item>,
+    perception: Perception,
+    energy: i32,
+    level: u32,
+    /// for the player the experience gathered towards the next level,
+    /// for monsters the experience they grant when killed
+    xp: i32,
+    /// progress towards the next regenerated hit point
+    regeneration: i32,
+    effects: Vec<StatusEffect>,
+    /// effect the unit's attacks put on whoever they damage
+    on_hit: Option<StatusEffect>,
 }
 
 impl Unit {
-    pub fn new(
-        x: i32,
-        y: i32,
-        char: char,
-        color: Color,
-        name: &str,
-        blocks_point: bool,
-        alive: bool,
-        spawn_room: u32,
-        ai: AI,
-        max_hp: i32,
-        current_hp: i32,
-        defense: i32,
-        damage: i32,
-    ) -> Self {
-        Unit {
-            position: Coordinates { x, y },
-            char,
-            color,
-            alive,
-            blocks_point,
-            name: String::from(name),
-            spawn_room,
-            stats: UnitStats {
-                max_hp,
-                current_hp,
-                defense,
-                damage,
-            },
-            ai,
-        }
-    }
-
     /// move by the given amount
     pub fn r#move(&mut self, x: i32, y: i32) {
         self.position.x += x;
         self.position.y += y;
     }
 
-    /// set the color and then draw the character that represents this object at its position
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.color);
-        con.put_char(
-            self.position.x,
-            self.position.y,
-            self.char,
-            BackgroundFlag::None,
-        );
+    /// draw the character that represents this object at its position, in its color
+    pub fn draw(&self, renderer: &mut dyn Renderer) {
+        renderer.put_char(self.position.x, self.position.y, self.char, self.color);
     }
 
     pub fn monster_step(&self, target: &Coordinates) -> (f32, i32, i32) {
@@ -118,41 +175,427 @@ impl Unit {
         (distance, dx, dy)
     }
 
-    pub fn take_damage(&mut self, damage: i32) {
+    pub fn distance_to(&self, target: &Coordinates) -> f32 {
+        let dx = target.x - self.position.x;
+        let dy = target.y - self.position.y;
+
+        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+    }
+
+    /// restore hit points, never above the maximum
+    pub fn heal(&mut self, amount: i32) {
+        self.stats.current_hp = (self.stats.current_hp + amount).min(self.max_hp());
+    }
+
+    /// put the item into the inventory, handing it back if there is no room left
+    pub fn add_item(&mut self, item: Item) -> Result<(), Item> {
+        if self.inventory.len() >= self.inventory_capacity {
+            return Err(item);
+        }
+
+        self.inventory.push(item);
+
+        Ok(())
+    }
+
+    pub fn remove_item(&mut self, index: usize) -> Item {
+        self.inventory.remove(index)
+    }
+
+    pub fn inventory(&self) -> &Vec<Item> {
+        &self.inventory
+    }
+
+    pub fn equipment(&self) -> &Vec<Item> {
+        &self.equipment
+    }
+
+    /// body armour drags its wearer under in deep water
+    pub fn wears_body_armor(&self) -> bool {
+        self.equipment
+            .iter()
+            .filter_map(Item::equipment)
+            .any(|equipment| equipment.slot == Slot::Body)
+    }
+
+    fn equipment_bonus(&self, bonus: impl Fn(Equipment) -> i32) -> i32 {
+        self.equipment
+            .iter()
+            .filter_map(Item::equipment)
+            .map(bonus)
+            .sum()
+    }
+
+    /// wear the inventory item in its slot; when the slot is full the item worn there the longest
+    /// goes back into the inventory and its name is returned
+    pub fn equip(&mut self, index: usize) -> Option<String> {
+        let slot = self.inventory[index].equipment()?.slot;
+        let item = self.inventory.remove(index);
+
+        let is_in_slot =
+            |item: &Item| item.equipment().map(|equipment| equipment.slot) == Some(slot);
+
+        let mut replaced = None;
+
+        if self
+            .equipment
+            .iter()
+            .filter(|item| is_in_slot(item))
+            .count()
+            >= slot.capacity()
+        {
+            if let Some(replaced_id) = self.equipment.iter().position(is_in_slot) {
+                let item = self.equipment.remove(replaced_id);
+
+                replaced = Some(item.name().clone());
+                self.inventory.push(item);
+            }
+        }
+
+        self.equipment.push(item);
+
+        replaced
+    }
+
+    /// take the worn item off and put it back into the inventory
+    pub fn unequip(&mut self, index: usize) -> bool {
+        if self.inventory.len() >= self.inventory_capacity {
+            return false;
+        }
+
+        let item = self.equipment.remove(index);
+        self.inventory.push(item);
+
+        self.stats.current_hp = self.stats.current_hp.min(self.max_hp());
+
+        true
+    }
+
+    pub fn speed(&self) -> i32 {
+        self.effects
+            .iter()
+            .fold(self.stats.speed, |speed, effect| effect.modify_speed(speed))
+    }
+
+    pub fn gain_energy(&mut self) {
+        self.energy += self.speed();
+    }
+
+    pub fn spend_energy(&mut self, cost: i32) {
+        self.energy -= cost;
+    }
+
+    pub fn can_act(&self) -> bool {
+        self.energy >= ACTION_ENERGY
+    }
+
+    pub fn effects(&self) -> &Vec<StatusEffect> {
+        &self.effects
+    }
+
+    /// put the effect on the unit, an effect of the same kind already on it lasts and hits as
+    /// long and hard as the stronger of the two
+    pub fn add_effect(&mut self, effect: StatusEffect, messages: &mut Vec<Message>) {
+        if let Some(current) = self.effects.iter_mut().find(|e| e.kind == effect.kind) {
+            current.turns = current.turns.max(effect.turns);
+            current.power = current.power.max(effect.power);
+
+            return;
+        }
+
+        messages.push(Message::new(
+            format!("{} is {}", self.name, effect.label()),
+            color::LIGHT_YELLOW,
+        ));
+
+        self.effects.push(effect);
+    }
+
+    /// called when the unit is about to act, returns `false` when an effect makes it lose the turn
+    pub fn start_turn(&mut self, messages: &mut Vec<Message>) -> bool {
+        let skips = self.effects.iter().any(StatusEffect::on_turn_start);
+
+        if skips && self.ai == AI::Player {
+            messages.push(Message::new(
+                "You are stunned and lose your turn",
+                color::LIGHT_YELLOW,
+            ));
+        }
+
+        !skips
+    }
+
+    /// called once the unit acted or lost its turn: the effects work on it and wear off
+    pub fn end_turn(&mut self, messages: &mut Vec<Message>) {
+        for effect in self.effects.clone() {
+            if !self.alive {
+                return;
+            }
+
+            let change = effect.on_turn_end();
+
+            if change > 0 {
+                self.heal(change);
+            } else if change < 0 {
+                if self.ai == AI::Player {
+                    messages.push(Message::new(
+                        format!("You lose {} hit points while {}", -change, effect.label()),
+                        color::LIGHT_RED,
+                    ));
+                }
+
+                self.take_damage(-change, messages);
+            }
+        }
+
+        for effect in &mut self.effects {
+            effect.turns -= 1;
+
+            if effect.turns <= 0 && self.ai == AI::Player {
+                messages.push(Message::new(
+                    format!("You are no longer {}", effect.label()),
+                    color::LIGHT_GREY,
+                ));
+            }
+        }
+
+        self.effects.retain(|effect| effect.turns > 0);
+    }
+
+    /// heal a bit over one tick of the scheduler, all of the max HP over `REGENERATION_TURNS`
+    /// turns at normal speed, one hit point at a time
+    pub fn regenerate(&mut self) {
+        if !self.alive || self.stats.current_hp >= self.max_hp() {
+            self.regeneration = 0;
+
+            return;
+        }
+
+        let ticks_per_hp = REGENERATION_TURNS * ACTION_ENERGY / NORMAL_SPEED;
+        self.regeneration += self.max_hp();
+
+        while self.regeneration >= ticks_per_hp {
+            self.regeneration -= ticks_per_hp;
+            self.heal(1);
+        }
+    }
+
+    pub fn perception(&self) -> &Perception {
+        &self.perception
+    }
+
+    pub fn set_state(&mut self, state: MonsterState) {
+        self.perception.state = state;
+    }
+
+    /// update the state from what the unit sees this turn: `seen_player` is the player's position
+    /// if the player is in sight, and `wakes` whether a sleeping unit notices it.
+    /// Returns `true` when the unit has just started hunting
+    pub fn perceive(&mut self, seen_player: Option<Coordinates>, wakes: bool) -> bool {
+        let perception = &mut self.perception;
+        let was_hunting = perception.state == MonsterState::Hunting;
+
+        match (perception.state, seen_player) {
+            (MonsterState::Sleeping, Some(_)) if !wakes => {}
+            (MonsterState::Sleeping, None) => {}
+
+            (_, Some(position)) => {
+                perception.state = MonsterState::Hunting;
+                perception.last_known_player_pos = Some(position);
+                perception.search_turns = SEARCH_TURNS;
+            }
+
+            (MonsterState::Hunting, None) => {
+                perception.state = MonsterState::Searching;
+                perception.search_turns = SEARCH_TURNS;
+            }
+
+            (MonsterState::Searching, None) => {
+                perception.search_turns -= 1;
+
+                if perception.search_turns <= 0 {
+                    perception.state = MonsterState::Wandering;
+                    perception.last_known_player_pos = None;
+                }
+            }
+
+            (MonsterState::Wandering, None) => {}
+        }
+
+        !was_hunting && perception.state == MonsterState::Hunting
+    }
+
+    /// make a unit that was attacked look for the attacker, waking it up if needed
+    pub fn alert(&mut self, attacker: Coordinates) {
+        if self.perception.state != MonsterState::Hunting {
+            self.perception.state = MonsterState::Searching;
+            self.perception.search_turns = SEARCH_TURNS;
+        }
+
+        self.perception.last_known_player_pos = Some(attacker);
+    }
+
+    /// forget the last known position once it has been reached
+    pub fn forget_player(&mut self) {
+        self.perception.last_known_player_pos = None;
+    }
+
+    /// returns the experience the unit was worth if this damage killed it
+    pub fn take_damage(&mut self, damage: i32, messages: &mut Vec<Message>) -> Option<i32> {
         self.stats.current_hp -= damage;
 
-        if self.stats.current_hp <= 0 {
+        if self.stats.current_hp <= 0 && self.alive {
             self.alive = false;
             self.blocks_point = false;
 
-            println!("{} is dead", self.name);
+            let color = if self.ai == AI::Player {
+                color::DARK_RED
+            } else {
+                color::ORANGE
+            };
+
+            messages.push(Message::new(format!("{} is dead", self.name), color));
+
+            self.color = color::GREY;
+
+            return Some(self.xp);
+        }
+
+        None
+    }
 
-            self.color = colors::GREY;
+    /// damage a hit that would deal `damage` does to the unit with its effects
+    pub fn damage_from_hit(&self, damage: i32) -> i32 {
+        if damage <= 0 {
+            return damage;
         }
+
+        self.effects
+            .iter()
+            .fold(damage, |damage, effect| effect.on_damage(damage))
+    }
+
+    pub fn attack(&mut self, target: &mut Unit, messages: &mut Vec<Message>) {
+        let name = self.name.clone();
+
+        self.attack_with(target, self.damage(), &name, messages);
     }
 
-    pub fn attack(&self, target: &mut Unit) {
-        let damage = self.stats.damage - target.defense();
+    /// hit `target` from afar, with the ranged weapon the unit wears or its own skill
+    pub fn shoot(&mut self, target: &mut Unit, messages: &mut Vec<Message>) {
+        let source = self
+            .equipment
+            .iter()
+            .find(|item| {
+                item.equipment()
+                    .is_some_and(|equipment| equipment.range > 0)
+            })
+            .map_or_else(|| self.name.clone(), |weapon| weapon.name().clone());
+
+        self.attack_with(target, self.ranged_damage(), &source, messages);
+    }
+
+    /// hit `target` with `power` reduced by its defense, `source` is what the hit comes from,
+    /// like the unit itself or the knife it threw
+    pub fn attack_with(
+        &mut self,
+        target: &mut Unit,
+        power: i32,
+        source: &str,
+        messages: &mut Vec<Message>,
+    ) {
+        let power = self
+            .effects
+            .iter()
+            .fold(power, |power, effect| effect.on_attack(power));
+        let damage = target.damage_from_hit(power - target.defense());
+
+        // hits on the player are highlighted so they stand out in the log
+        let hit_color = if target.ai == AI::Player {
+            color::LIGHT_RED
+        } else {
+            color::WHITE
+        };
 
         if damage > 0 {
-            target.take_damage(damage);
-
-            println!("{} got {} damage from {}", target.name(), damage, self.name);
-        } else if self.stats.damage <= 0 {
-            println!(
-                "{} tries to attack {} but it is too weak to deal damage",
-                self.name,
-                target.name()
-            );
+            messages.push(Message::new(
+                format!("{} got {} damage from {}", target.name(), damage, source),
+                hit_color,
+            ));
+
+            if let Some(xp) = target.take_damage(damage, messages) {
+                if self.ai == AI::Player {
+                    self.gain_xp(xp, messages);
+                }
+            } else if let Some(effect) = self.on_hit {
+                target.add_effect(effect, messages);
+            }
+        } else if power <= 0 {
+            messages.push(Message::new(
+                format!(
+                    "{} tries to attack {} but it is too weak to deal damage",
+                    source,
+                    target.name()
+                ),
+                color::LIGHT_GREY,
+            ));
         } else {
-            println!(
-                "{} attacks {} but all damage was absorbed by armor",
-                self.name,
-                target.name()
-            );
+            messages.push(Message::new(
+                format!(
+                    "{} attacks {} but all damage was absorbed by armor",
+                    source,
+                    target.name()
+                ),
+                color::LIGHT_GREY,
+            ));
         }
     }
 
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn xp(&self) -> i32 {
+        self.xp
+    }
+
+    /// experience needed to reach the next level
+    pub fn xp_to_level_up(&self) -> i32 {
+        LEVEL_UP_BASE + self.level as i32 * LEVEL_UP_FACTOR
+    }
+
+    pub fn gain_xp(&mut self, xp: i32, messages: &mut Vec<Message>) {
+        self.xp += xp;
+
+        messages.push(Message::new(
+            format!("You gain {} experience points", xp),
+            color::LIGHT_GREEN,
+        ));
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.xp >= self.xp_to_level_up()
+    }
+
+    /// spend the experience of one level on raising `stat`
+    pub fn level_up(&mut self, stat: Stat) {
+        self.xp -= self.xp_to_level_up();
+        self.level += 1;
+
+        match stat {
+            Stat::MaxHp => {
+                self.stats.max_hp += LEVEL_UP_MAX_HP;
+                self.stats.current_hp += LEVEL_UP_MAX_HP;
+            }
+            Stat::Damage => self.stats.damage += LEVEL_UP_DAMAGE,
+            Stat::Defense => self.stats.defense += LEVEL_UP_DEFENSE,
+        }
+    }
+
+    pub fn set_position(&mut self, position: Coordinates) {
+        self.position = position;
+    }
+
     pub fn get_position(&self) -> &Coordinates {
         &self.position
     }
@@ -169,21 +612,119 @@ impl Unit {
         &self.name
     }
 
-    pub fn spawn_room(&self) -> u32 {
-        self.spawn_room
+    /// damage of a melee hit, a weapon made for shooting is no help up close
+    pub fn damage(&self) -> i32 {
+        self.stats.damage
+            + self.equipment_bonus(|equipment| match equipment.range {
+                0 => equipment.damage,
+                _ => 0,
+            })
+    }
+
+    /// damage of a shot, only ranged weapons add to it
+    pub fn ranged_damage(&self) -> i32 {
+        self.stats.damage
+            + self.equipment_bonus(|equipment| match equipment.range {
+                0 => 0,
+                _ => equipment.damage,
+            })
     }
 
     pub fn defense(&self) -> i32 {
-        self.stats.defense
+        let defense = self.stats.defense + self.equipment_bonus(|equipment| equipment.defense);
+
+        self.effects
+            .iter()
+            .fold(defense, |defense, effect| effect.modify_defense(defense))
     }
 
     pub fn max_hp(&self) -> i32 {
+        self.stats.max_hp + self.equipment_bonus(|equipment| equipment.max_hp)
+    }
+
+    /// how far the unit can shoot, with its own skill or the farthest reaching weapon it wears
+    pub fn range(&self) -> i32 {
+        self.equipment
+            .iter()
+            .filter_map(Item::equipment)
+            .map(|equipment| equipment.range)
+            .fold(self.stats.range, i32::max)
+    }
+
+    pub fn base_damage(&self) -> i32 {
+        self.stats.damage
+    }
+
+    pub fn base_defense(&self) -> i32 {
+        self.stats.defense
+    }
+
+    pub fn base_max_hp(&self) -> i32 {
         self.stats.max_hp
     }
 
     pub fn current_hp(&self) -> i32 {
         self.stats.current_hp
     }
+
+    /// how hurt the unit looks, without giving away its exact hit points
+    fn health_description(&self) -> &'static str {
+        let share = self.stats.current_hp as f32 / self.max_hp() as f32;
+
+        match share {
+            _ if !self.alive => "dead",
+            share if share >= 1.0 => "unhurt",
+            share if share > 0.66 => "lightly wounded",
+            share if share > 0.33 => "wounded",
+            share if share > 0.1 => "badly wounded",
+            _ => "nearly dead",
+        }
+    }
+
+    /// what the unit is busy with, as far as the player can tell
+    fn status_descriptions(&self) -> Vec<&'static str> {
+        let mut statuses = vec![];
+
+        if self.ai != AI::Player {
+            match self.perception.state {
+                MonsterState::Sleeping => statuses.push("asleep"),
+                MonsterState::Hunting => statuses.push("hunting you"),
+                MonsterState::Searching => statuses.push("searching for you"),
+                MonsterState::Wandering => {}
+            }
+        }
+
+        statuses.extend(self.effects.iter().map(StatusEffect::label));
+
+        statuses
+    }
+
+    /// what the player calls the unit on screen
+    pub fn display_name(&self) -> String {
+        if !self.alive {
+            format!("the remains of {}", self.name)
+        } else if self.ai == AI::Player {
+            String::from("you")
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// one line about the unit for the look mode
+    pub fn description(&self) -> String {
+        if !self.alive {
+            return self.display_name();
+        }
+
+        let mut description = format!("{}, {}", self.display_name(), self.health_description());
+
+        for status in self.status_descriptions() {
+            description.push_str(", ");
+            description.push_str(status);
+        }
+
+        description
+    }
 }
 
 impl Unit {
@@ -191,7 +732,7 @@ impl Unit {
         Self {
             position: Coordinates { x, y },
             char: '@',
-            color: colors::WHITE,
+            color: color::WHITE,
             name: "Player".into(),
             blocks_point: true,
             alive: true,
@@ -202,45 +743,50 @@ impl Unit {
                 current_hp: 30,
                 defense: 2,
                 damage: 5,
+                speed: NORMAL_SPEED,
+                range: 0,
             },
+            inventory: vec![],
+            inventory_capacity: PLAYER_INVENTORY_CAPACITY,
+            equipment: vec![],
+            perception: Perception::new(MonsterState::Hunting, 10),
+            energy: ACTION_ENERGY,
+            level: 1,
+            xp: 0,
+            regeneration: 0,
+            effects: vec![],
+            on_hit: None,
         }
     }
 
-    pub fn orc(x: i32, y: i32, spawn_room: u32) -> Self {
-        Self {
-            position: Coordinates { x, y },
-            char: 'o',
-            color: colors::DESATURATED_GREEN,
-            alive: true,
-            blocks_point: true,
-            name: "Orc".into(),
-            spawn_room,
-            ai: AI::Basic,
-            stats: UnitStats {
-                max_hp: 10,
-                current_hp: 10,
-                defense: 0,
-                damage: 3,
-            },
-        }
-    }
-
-    pub fn troll(x: i32, y: i32, spawn_room: u32) -> Self {
+    pub fn from_template(template: &MonsterTemplate, x: i32, y: i32, spawn_room: u32) -> Self {
         Self {
             position: Coordinates { x, y },
-            char: 'T',
-            color: colors::DARK_GREEN,
+            char: template.glyph,
+            color: template.color,
             alive: true,
             blocks_point: true,
-            name: "Troll".into(),
+            name: template.name.clone(),
             spawn_room,
-            ai: AI::Basic,
+            ai: template.ai,
             stats: UnitStats {
-                max_hp: 16,
-                current_hp: 16,
-                defense: 1,
-                damage: 4,
+                max_hp: template.max_hp,
+                current_hp: template.max_hp,
+                defense: template.defense,
+                damage: template.damage,
+                speed: template.speed,
+                range: template.range,
             },
+            inventory: vec![],
+            inventory_capacity: 0,
+            equipment: vec![],
+            perception: Perception::new(MonsterState::Sleeping, template.sight_radius),
+            energy: 0,
+            level: 1,
+            xp: template.xp,
+            regeneration: 0,
+            effects: vec![],
+            on_hit: template.on_hit,
         }
     }
 }